@@ -0,0 +1,739 @@
+use anyhow::bail;
+use anyhow::Result;
+use std::convert::TryInto;
+
+/*
+	* * ASEPRITE FILE READER
+	* Format reference: https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md
+	*/
+
+pub const HEADER_MAGIC: u16 = 0xA5E0;
+pub const FRAME_MAGIC: u16 = 0xF1FA;
+
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+const LAYER_FLAG_VISIBLE: u16 = 1 << 0;
+const LAYER_TYPE_GROUP: u16 = 1;
+
+const CEL_RAW: u16 = 0;
+const CEL_LINKED: u16 = 1;
+const CEL_COMPRESSED: u16 = 2;
+
+const DEPTH_RGBA: u16 = 32;
+const DEPTH_GRAYSCALE: u16 = 16;
+const DEPTH_INDEXED: u16 = 8;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AseLayer {
+	pub name: String,
+	pub visible: bool,
+	pub is_group: bool,
+	pub child_level: u16,
+	pub opacity: u8,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AseCel {
+	pub layer: usize,
+	pub x: i32,
+	pub y: i32,
+	pub opacity: u8,
+	pub width: u32,
+	pub height: u32,
+	///Always RGBA, converted from whatever depth the file uses.
+	pub pixels: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AseTag {
+	pub name: String,
+	pub from: u32,
+	pub to: u32,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AsepriteFile {
+	pub width: u32,
+	pub height: u32,
+	pub layers: Vec<AseLayer>,
+	pub tags: Vec<AseTag>,
+	///Cels of each frame, in the order they appear in the file.
+	pub frames: Vec<Vec<AseCel>>,
+}
+
+///Checks the magic number of the header to tell whether the bytes are an Aseprite file.
+pub fn is_aseprite(bytes: &[u8]) -> bool {
+	bytes.len() >= HEADER_SIZE && read_u16(bytes, 4) == HEADER_MAGIC
+}
+
+pub fn read_aseprite(bytes: &[u8]) -> Result<AsepriteFile> {
+	if !is_aseprite(bytes) {
+		bail!("Not an Aseprite file, wrong header magic number.");
+	};
+	let frame_count = read_u16(bytes, 6) as usize;
+	let width = read_u16(bytes, 8) as u32;
+	let height = read_u16(bytes, 10) as u32;
+	let color_depth = read_u16(bytes, 12);
+	let transparent_index = bytes[28];
+	if color_depth != DEPTH_RGBA && color_depth != DEPTH_GRAYSCALE && color_depth != DEPTH_INDEXED {
+		bail!("Unsupported Aseprite color depth: {}", color_depth);
+	};
+
+	let mut file = AsepriteFile {
+		width,
+		height,
+		..Default::default()
+	};
+	let mut palette: Vec<[u8; 4]> = vec![[0, 0, 0, 0]; 256];
+	// Indexed cels need the palette, which may come later in the same frame, so they are kept raw until the frame ends.
+	let mut pending_cels: Vec<(AseCel, bool)> = vec![];
+
+	let mut offset = HEADER_SIZE;
+	for frame in 0..frame_count {
+		if offset + FRAME_HEADER_SIZE > bytes.len() {
+			bail!("Aseprite file truncated at frame {}", frame);
+		};
+		let frame_size = read_u32(bytes, offset) as usize;
+		if read_u16(bytes, offset + 4) != FRAME_MAGIC {
+			bail!("Wrong magic number in the header of frame {}", frame);
+		};
+		let old_chunk_count = read_u16(bytes, offset + 6) as usize;
+		let new_chunk_count = read_u32(bytes, offset + 12) as usize;
+		let chunk_count = if new_chunk_count == 0 {
+			old_chunk_count
+		} else {
+			new_chunk_count
+		};
+		let frame_end = offset + frame_size;
+		if frame_end > bytes.len() {
+			bail!("Aseprite file truncated at frame {}", frame);
+		};
+
+		let mut chunk_offset = offset + FRAME_HEADER_SIZE;
+		for _chunk in 0..chunk_count {
+			if chunk_offset + 6 > frame_end {
+				bail!("Malformed chunk in frame {}", frame);
+			};
+			let chunk_size = read_u32(bytes, chunk_offset) as usize;
+			let chunk_type = read_u16(bytes, chunk_offset + 4);
+			if chunk_size < 6 || chunk_offset + chunk_size > frame_end {
+				bail!("Malformed chunk in frame {}", frame);
+			};
+			let data = &bytes[chunk_offset + 6..chunk_offset + chunk_size];
+			match chunk_type {
+				CHUNK_LAYER => file.layers.push(read_layer_chunk(data)?),
+				CHUNK_CEL => {
+					if let Some(cel) = read_cel_chunk(data, frame, &file.frames)? {
+						pending_cels.push(cel);
+					};
+				}
+				CHUNK_TAGS => file.tags.extend(read_tags_chunk(data)?),
+				CHUNK_PALETTE => read_palette_chunk(data, &mut palette)?,
+				CHUNK_OLD_PALETTE => read_old_palette_chunk(data, &mut palette)?,
+				_ => (), // User data, slices, color profiles and the like are of no interest to us.
+			};
+			chunk_offset += chunk_size;
+		}
+
+		let mut frame_cels = vec![];
+		for (mut cel, needs_conversion) in pending_cels.drain(..) {
+			if needs_conversion {
+				cel.pixels = to_rgba(&cel.pixels, color_depth, &palette, transparent_index, frame)?;
+			};
+			// In 64 bits, as the largest cel a header can describe doesn't fit in 32.
+			if cel.pixels.len() as u64 != cel.width as u64 * cel.height as u64 * 4 {
+				bail!(
+					"Cel in frame {} holds the wrong amount of pixel data",
+					frame
+				);
+			};
+			frame_cels.push(cel);
+		}
+		file.frames.push(frame_cels);
+		offset = frame_end;
+	}
+	Ok(file)
+}
+
+impl AsepriteFile {
+	///Frame range of the tag with the given name, inclusive on both ends.
+	pub fn tag_range(&self, name: &str) -> Option<(u32, u32)> {
+		self.tags
+			.iter()
+			.find(|tag| tag.name == name)
+			.map(|tag| (tag.from, tag.to))
+	}
+
+	pub fn layer_index(&self, name: &str) -> Option<usize> {
+		self.layers.iter().position(|layer| layer.name == name)
+	}
+
+	///Whether the layer and every group containing it are visible.
+	pub fn is_layer_visible(&self, index: usize) -> bool {
+		let mut level = self.layers[index].child_level;
+		if !self.layers[index].visible {
+			return false;
+		};
+		for parent in self.layers[..index].iter().rev() {
+			if level == 0 {
+				break;
+			};
+			if parent.child_level < level {
+				if !parent.visible {
+					return false;
+				};
+				level = parent.child_level;
+			};
+		}
+		true
+	}
+
+	///Flattens a frame into a single image.
+	///If a layer is given only that one is drawn, whether it is visible or not. Otherwise every visible layer is, bottom to top.
+	pub fn render_frame(&self, frame: usize, layer: Option<usize>) -> image::RgbaImage {
+		let mut canvas = image::RgbaImage::new(self.width, self.height);
+		let mut cels: Vec<&AseCel> = self.frames[frame]
+			.iter()
+			.filter(|cel| match layer {
+				Some(index) => cel.layer == index,
+				None => cel.layer < self.layers.len() && self.is_layer_visible(cel.layer),
+			})
+			.collect();
+		cels.sort_by_key(|cel| cel.layer);
+		for cel in cels {
+			let layer_opacity = match self.layers.get(cel.layer) {
+				Some(thing) => thing.opacity as u32,
+				None => 255,
+			};
+			let opacity = cel.opacity as u32 * layer_opacity / 255;
+			for cel_y in 0..cel.height {
+				let canvas_y = cel.y + cel_y as i32;
+				if canvas_y < 0 || canvas_y >= self.height as i32 {
+					continue;
+				};
+				for cel_x in 0..cel.width {
+					let canvas_x = cel.x + cel_x as i32;
+					if canvas_x < 0 || canvas_x >= self.width as i32 {
+						continue;
+					};
+					let index = (cel_y as usize * cel.width as usize + cel_x as usize) * 4;
+					let source = &cel.pixels[index..index + 4];
+					let target = canvas.get_pixel_mut(canvas_x as u32, canvas_y as u32);
+					blend_normal(&mut target.0, source, opacity);
+				}
+			}
+		}
+		canvas
+	}
+}
+
+///Standard "source over" alpha compositing, the normal blend mode.
+fn blend_normal(target: &mut [u8; 4], source: &[u8], opacity: u32) {
+	let source_alpha = source[3] as u32 * opacity / 255;
+	if source_alpha == 0 {
+		return;
+	};
+	let target_alpha = target[3] as u32;
+	let out_alpha = source_alpha + target_alpha * (255 - source_alpha) / 255;
+	for channel in 0..3 {
+		let blended = (source[channel] as u32 * source_alpha
+			+ target[channel] as u32 * target_alpha * (255 - source_alpha) / 255)
+			/ out_alpha;
+		target[channel] = blended as u8;
+	}
+	target[3] = out_alpha as u8;
+}
+
+fn read_layer_chunk(data: &[u8]) -> Result<AseLayer> {
+	if data.len() < 18 {
+		bail!("Layer chunk too short");
+	};
+	let flags = read_u16(data, 0);
+	let layer_type = read_u16(data, 2);
+	let child_level = read_u16(data, 4);
+	let opacity = data[12];
+	let (name, _) = read_string(data, 16)?;
+	Ok(AseLayer {
+		name,
+		visible: flags & LAYER_FLAG_VISIBLE != 0,
+		is_group: layer_type == LAYER_TYPE_GROUP,
+		child_level,
+		opacity,
+	})
+}
+
+///Returns the cel and whether its pixels still have to be converted from the file's color depth.
+fn read_cel_chunk(
+	data: &[u8],
+	frame: usize,
+	previous_frames: &[Vec<AseCel>],
+) -> Result<Option<(AseCel, bool)>> {
+	if data.len() < 16 {
+		bail!("Cel chunk too short in frame {}", frame);
+	};
+	let layer = read_u16(data, 0) as usize;
+	let x = read_u16(data, 2) as i16 as i32;
+	let y = read_u16(data, 4) as i16 as i32;
+	let opacity = data[6];
+	let cel_type = read_u16(data, 7);
+	match cel_type {
+		CEL_RAW | CEL_COMPRESSED => {
+			if data.len() < 20 {
+				bail!("Cel chunk too short in frame {}", frame);
+			};
+			let width = read_u16(data, 16) as u32;
+			let height = read_u16(data, 18) as u32;
+			let pixels = if cel_type == CEL_RAW {
+				data[20..].to_vec()
+			} else {
+				match inflate::inflate_bytes_zlib(&data[20..]) {
+					Ok(thing) => thing,
+					Err(e) => bail!("Unable to decompress cel in frame {}: {}", frame, e),
+				}
+			};
+			Ok(Some((
+				AseCel {
+					layer,
+					x,
+					y,
+					opacity,
+					width,
+					height,
+					pixels,
+				},
+				true,
+			)))
+		}
+		CEL_LINKED => {
+			if data.len() < 18 {
+				bail!("Cel chunk too short in frame {}", frame);
+			};
+			let linked_frame = read_u16(data, 16) as usize;
+			let linked = match previous_frames.get(linked_frame) {
+				Some(thing) => thing.iter().find(|cel| cel.layer == layer),
+				None => None,
+			};
+			match linked {
+				// Linked cels share position and opacity with the original, not just the pixels.
+				Some(cel) => Ok(Some((cel.clone(), false))),
+				None => bail!(
+					"Linked cel in frame {} points to missing frame {}",
+					frame,
+					linked_frame
+				),
+			}
+		}
+		_ => Ok(None), // Tilemaps are not supported, skip them.
+	}
+}
+
+fn read_tags_chunk(data: &[u8]) -> Result<Vec<AseTag>> {
+	if data.len() < 10 {
+		bail!("Tags chunk too short");
+	};
+	let tag_count = read_u16(data, 0) as usize;
+	let mut tags = vec![];
+	let mut offset = 10;
+	for _tag in 0..tag_count {
+		if offset + 17 > data.len() {
+			bail!("Tags chunk too short");
+		};
+		let from = read_u16(data, offset) as u32;
+		let to = read_u16(data, offset + 2) as u32;
+		let (name, name_size) = read_string(data, offset + 17)?;
+		offset += 17 + name_size;
+		tags.push(AseTag { name, from, to });
+	}
+	Ok(tags)
+}
+
+fn read_palette_chunk(data: &[u8], palette: &mut Vec<[u8; 4]>) -> Result<()> {
+	if data.len() < 20 {
+		bail!("Palette chunk too short");
+	};
+	let new_size = read_u32(data, 0) as usize;
+	let first = read_u32(data, 4) as usize;
+	let last = read_u32(data, 8) as usize;
+	if palette.len() < new_size {
+		palette.resize(new_size, [0, 0, 0, 0]);
+	};
+	let mut offset = 20;
+	for index in first..=last {
+		if offset + 6 > data.len() || index >= palette.len() {
+			bail!("Palette chunk too short");
+		};
+		let flags = read_u16(data, offset);
+		palette[index] = [
+			data[offset + 2],
+			data[offset + 3],
+			data[offset + 4],
+			data[offset + 5],
+		];
+		offset += 6;
+		if flags & 1 != 0 {
+			let (_, name_size) = read_string(data, offset)?; // Skip the color name.
+			offset += name_size;
+		};
+	}
+	Ok(())
+}
+
+fn read_old_palette_chunk(data: &[u8], palette: &mut [[u8; 4]]) -> Result<()> {
+	if data.len() < 2 {
+		bail!("Old palette chunk too short");
+	};
+	let packet_count = read_u16(data, 0) as usize;
+	let mut offset = 2;
+	let mut index = 0;
+	for _packet in 0..packet_count {
+		if offset + 2 > data.len() {
+			bail!("Old palette chunk too short");
+		};
+		index += data[offset] as usize;
+		let color_count = match data[offset + 1] {
+			0 => 256,
+			count => count as usize,
+		};
+		offset += 2;
+		for _color in 0..color_count {
+			if offset + 3 > data.len() || index >= palette.len() {
+				bail!("Old palette chunk too short");
+			};
+			palette[index] = [data[offset], data[offset + 1], data[offset + 2], 255];
+			offset += 3;
+			index += 1;
+		}
+	}
+	Ok(())
+}
+
+fn to_rgba(
+	pixels: &[u8],
+	color_depth: u16,
+	palette: &[[u8; 4]],
+	transparent_index: u8,
+	frame: usize,
+) -> Result<Vec<u8>> {
+	let mut rgba = Vec::with_capacity(pixels.len() * 4);
+	match color_depth {
+		DEPTH_RGBA => rgba.extend_from_slice(pixels),
+		DEPTH_GRAYSCALE => {
+			for pixel in pixels.chunks_exact(2) {
+				rgba.extend_from_slice(&[pixel[0], pixel[0], pixel[0], pixel[1]]);
+			}
+		}
+		DEPTH_INDEXED => {
+			for index in pixels.iter() {
+				if *index == transparent_index {
+					rgba.extend_from_slice(&[0, 0, 0, 0]);
+					continue;
+				};
+				match palette.get(*index as usize) {
+					Some(color) => rgba.extend_from_slice(color),
+					None => bail!(
+						"Color index {} out of the palette in frame {}",
+						index,
+						frame
+					),
+				};
+			}
+		}
+		_ => bail!("Unsupported Aseprite color depth: {}", color_depth),
+	};
+	Ok(rgba)
+}
+
+///Returns the string, and how many bytes it takes in the file, length included.
+///Invalid UTF-8 is replaced, so the size of the returned string can differ from the one in the file.
+fn read_string(data: &[u8], offset: usize) -> Result<(String, usize)> {
+	if offset + 2 > data.len() {
+		bail!("String out of bounds");
+	};
+	let length = read_u16(data, offset) as usize;
+	if offset + 2 + length > data.len() {
+		bail!("String out of bounds");
+	};
+	let text = String::from_utf8_lossy(&data[offset + 2..offset + 2 + length]).to_string();
+	Ok((text, 2 + length))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+	u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	///Layered, with raw, compressed and linked cels, a palette and tags, some of their names not valid UTF-8.
+	///Its visible layers add up to the "4 corners" example's input.
+	const FIXTURE: &[u8] = include_bytes!("../examples/aseprite/tiles.aseprite");
+	const FIXTURE_RENDER: &[u8] = include_bytes!("../examples/4 corners/color-4-corners-input.png");
+
+	fn string(text: &[u8]) -> Vec<u8> {
+		let mut bytes = (text.len() as u16).to_le_bytes().to_vec();
+		bytes.extend_from_slice(text);
+		bytes
+	}
+
+	fn chunk(chunk_type: u16, data: &[u8]) -> Vec<u8> {
+		let mut bytes = (6 + data.len() as u32).to_le_bytes().to_vec();
+		bytes.extend_from_slice(&chunk_type.to_le_bytes());
+		bytes.extend_from_slice(data);
+		bytes
+	}
+
+	fn raw_cel(layer: u16, width: u16, height: u16, pixels: &[u8]) -> Vec<u8> {
+		let mut data = layer.to_le_bytes().to_vec();
+		data.extend_from_slice(&[0, 0, 0, 0, 255]); // Position and opacity.
+		data.extend_from_slice(&CEL_RAW.to_le_bytes());
+		data.extend_from_slice(&[0; 7]);
+		data.extend_from_slice(&width.to_le_bytes());
+		data.extend_from_slice(&height.to_le_bytes());
+		data.extend_from_slice(pixels);
+		chunk(CHUNK_CEL, &data)
+	}
+
+	///Palette chunk starting at index 0, each color with an optional name.
+	fn palette(colors: &[([u8; 4], Option<&[u8]>)]) -> Vec<u8> {
+		let mut data = (colors.len() as u32).to_le_bytes().to_vec();
+		data.extend_from_slice(&0u32.to_le_bytes());
+		data.extend_from_slice(&(colors.len() as u32 - 1).to_le_bytes());
+		data.extend_from_slice(&[0; 8]);
+		for (color, name) in colors.iter() {
+			data.extend_from_slice(&(name.is_some() as u16).to_le_bytes());
+			data.extend_from_slice(color);
+			if let Some(name) = name {
+				data.extend(string(name));
+			};
+		}
+		chunk(CHUNK_PALETTE, &data)
+	}
+
+	///A file made of the given chunks, one list of them per frame.
+	fn ase_file(color_depth: u16, width: u16, height: u16, frames: &[Vec<Vec<u8>>]) -> Vec<u8> {
+		let mut body = vec![];
+		for chunks in frames.iter() {
+			let frame_data: Vec<u8> = chunks.concat();
+			body.extend_from_slice(&((FRAME_HEADER_SIZE + frame_data.len()) as u32).to_le_bytes());
+			body.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+			body.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+			body.extend_from_slice(&[100, 0, 0, 0]); // Duration and reserved bytes.
+			body.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+			body.extend(frame_data);
+		}
+		let mut bytes = vec![0; HEADER_SIZE];
+		bytes[0..4].copy_from_slice(&((HEADER_SIZE + body.len()) as u32).to_le_bytes());
+		bytes[4..6].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+		bytes[6..8].copy_from_slice(&(frames.len() as u16).to_le_bytes());
+		bytes[8..10].copy_from_slice(&width.to_le_bytes());
+		bytes[10..12].copy_from_slice(&height.to_le_bytes());
+		bytes[12..14].copy_from_slice(&color_depth.to_le_bytes());
+		bytes.extend(body);
+		bytes
+	}
+
+	fn layer_chunk(name: &[u8]) -> Vec<u8> {
+		let mut data = vec![LAYER_FLAG_VISIBLE as u8, 0];
+		data.extend_from_slice(&[0; 10]);
+		data.extend_from_slice(&[255, 0, 0, 0]);
+		data.extend(string(name));
+		chunk(CHUNK_LAYER, &data)
+	}
+
+	#[test]
+	fn fixture_header() {
+		assert!(is_aseprite(FIXTURE));
+		let file = read_aseprite(FIXTURE).unwrap();
+		assert_eq!((file.width, file.height), (128, 32));
+		assert_eq!(file.frames.len(), 2);
+	}
+
+	#[test]
+	fn fixture_layers() {
+		let file = read_aseprite(FIXTURE).unwrap();
+		let layers: Vec<(&str, bool, bool, u16)> = file
+			.layers
+			.iter()
+			.map(|layer| {
+				(
+					layer.name.as_str(),
+					layer.visible,
+					layer.is_group,
+					layer.child_level,
+				)
+			})
+			.collect();
+		assert_eq!(
+			layers,
+			vec![
+				("base", true, false, 0),
+				("group", true, true, 0),
+				("detail", true, false, 1),
+				("sketch", false, false, 0),
+			]
+		);
+		assert_eq!(file.layer_index("detail"), Some(2));
+		let visible: Vec<bool> = (0..file.layers.len())
+			.map(|index| file.is_layer_visible(index))
+			.collect();
+		assert_eq!(visible, vec![true, true, true, false]);
+	}
+
+	#[test]
+	fn fixture_cels() {
+		let file = read_aseprite(FIXTURE).unwrap();
+		let cels: Vec<(usize, i32, i32, u32, u32)> = file.frames[0]
+			.iter()
+			.map(|cel| (cel.layer, cel.x, cel.y, cel.width, cel.height))
+			.collect();
+		// A raw cel, a compressed one, and one of the hidden layer.
+		assert_eq!(
+			cels,
+			vec![(0, 0, 0, 64, 32), (2, 64, 0, 64, 32), (3, 8, 8, 16, 16)]
+		);
+		// The second frame links back to the first one's base cel.
+		assert_eq!(file.frames[1][0], file.frames[0][0]);
+		assert_eq!(file.frames[1][1].layer, 2);
+		assert_ne!(file.frames[1][1].pixels, file.frames[0][1].pixels);
+	}
+
+	#[test]
+	fn fixture_renders_its_visible_layers() {
+		let file = read_aseprite(FIXTURE).unwrap();
+		let expected = image::load_from_memory(FIXTURE_RENDER)
+			.unwrap()
+			.into_rgba8();
+		assert!(file.render_frame(0, None) == expected);
+		// A single layer is drawn even if hidden.
+		let sketch = file.render_frame(0, Some(3));
+		assert_eq!(sketch.get_pixel(8, 8).0, [255, 0, 255, 255]);
+		assert_eq!(sketch.get_pixel(24, 24).0, [0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn fixture_tags() {
+		let file = read_aseprite(FIXTURE).unwrap();
+		// The first name isn't valid UTF-8, and the one after it must still be read from the right place.
+		assert_eq!(
+			file.tags,
+			vec![
+				AseTag {
+					name: "\u{FFFD}clair".to_string(),
+					from: 1,
+					to: 1,
+				},
+				AseTag {
+					name: "idle".to_string(),
+					from: 0,
+					to: 0,
+				},
+			]
+		);
+		assert_eq!(file.tag_range("idle"), Some((0, 0)));
+		assert_eq!(file.tag_range("missing"), None);
+	}
+
+	#[test]
+	fn indexed_cels_use_the_palette() {
+		let bytes = ase_file(
+			DEPTH_INDEXED,
+			4,
+			1,
+			&[vec![
+				layer_chunk(b"layer"),
+				// Invalid UTF-8 in the first name must not shift the colors after it.
+				palette(&[
+					([0, 0, 0, 255], Some(b"\xff\xfe\xfd")),
+					([255, 0, 0, 255], Some(b"red")),
+					([0, 255, 0, 128], None),
+					([0, 0, 255, 255], Some(b"blue")),
+				]),
+				raw_cel(0, 4, 1, &[1, 2, 3, 0]),
+			]],
+		);
+		let file = read_aseprite(&bytes).unwrap();
+		// Index 0 is the transparent one.
+		assert_eq!(
+			file.frames[0][0].pixels,
+			vec![255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 255, 0, 0, 0, 0]
+		);
+	}
+
+	#[test]
+	fn old_palette_chunks_are_read() {
+		// One packet, skipping index 0, with two colors.
+		let data = [1, 0, 1, 2, 10, 20, 30, 40, 50, 60];
+		let bytes = ase_file(
+			DEPTH_INDEXED,
+			2,
+			1,
+			&[vec![
+				layer_chunk(b"layer"),
+				chunk(CHUNK_OLD_PALETTE, &data),
+				raw_cel(0, 2, 1, &[1, 2]),
+			]],
+		);
+		let file = read_aseprite(&bytes).unwrap();
+		assert_eq!(
+			file.frames[0][0].pixels,
+			vec![10, 20, 30, 255, 40, 50, 60, 255]
+		);
+	}
+
+	#[test]
+	fn grayscale_cels_are_converted() {
+		let bytes = ase_file(
+			DEPTH_GRAYSCALE,
+			2,
+			1,
+			&[vec![
+				layer_chunk(b"layer"),
+				raw_cel(0, 2, 1, &[100, 255, 200, 50]),
+			]],
+		);
+		let file = read_aseprite(&bytes).unwrap();
+		assert_eq!(
+			file.frames[0][0].pixels,
+			vec![100, 100, 100, 255, 200, 200, 200, 50]
+		);
+	}
+
+	#[test]
+	fn malformed_files_are_refused() {
+		assert!(!is_aseprite(b"\x89PNG"));
+		assert!(read_aseprite(&[0; HEADER_SIZE]).is_err());
+		// Cut short in the middle of the last frame.
+		assert!(read_aseprite(&FIXTURE[..FIXTURE.len() - 10]).is_err());
+		// A cel holding less pixel data than its size says.
+		let bytes = ase_file(
+			DEPTH_RGBA,
+			2,
+			2,
+			&[vec![layer_chunk(b"layer"), raw_cel(0, 2, 2, &[0; 4])]],
+		);
+		assert!(read_aseprite(&bytes).is_err());
+		// A cel claiming to be as large as a header allows.
+		let bytes = ase_file(
+			DEPTH_RGBA,
+			2,
+			2,
+			&[vec![
+				layer_chunk(b"layer"),
+				raw_cel(0, u16::MAX, u16::MAX, &[0; 16]),
+			]],
+		);
+		assert!(read_aseprite(&bytes).is_err());
+	}
+}
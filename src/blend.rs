@@ -0,0 +1,117 @@
+use image::GenericImage;
+use image::GenericImageView;
+use image::Pixel;
+
+///How the colors of a layer are combined with those of the image below it.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum BlendMode {
+	///The layer is simply drawn over the image.
+	#[default]
+	Normal,
+	///Darkens the image, useful for shadows and grime.
+	Multiply,
+	///Brightens the image by adding the colors up, useful for glows.
+	Additive,
+	///Brightens the image more softly than additive, useful for lighting.
+	Screen,
+}
+
+impl BlendMode {
+	pub fn from_name(name: &str) -> Option<BlendMode> {
+		match name {
+			"normal" => Some(BlendMode::Normal),
+			"multiply" => Some(BlendMode::Multiply),
+			"additive" | "add" => Some(BlendMode::Additive),
+			"screen" => Some(BlendMode::Screen),
+			_ => None,
+		}
+	}
+
+	///Blends a single color channel. Both values range from 0 to 1.
+	fn blend_channel(&self, below: f32, above: f32) -> f32 {
+		match self {
+			BlendMode::Normal => above,
+			BlendMode::Multiply => below * above,
+			BlendMode::Additive => (below + above).min(1.0),
+			BlendMode::Screen => below + above - below * above,
+		}
+	}
+}
+
+///Draws the layer over the base image at the given offset, like imageops::overlay does, but using the given blend mode.
+///The opacity multiplies the layer's alpha, and the tint (if any) multiplies its colors.
+pub fn composite(
+	base: &mut image::DynamicImage,
+	layer: &image::DynamicImage,
+	x: u32,
+	y: u32,
+	mode: BlendMode,
+	opacity: f32,
+	tint: Option<image::Rgba<u8>>,
+) {
+	let (base_width, base_height) = base.dimensions();
+	let (layer_width, layer_height) = layer.dimensions();
+	for layer_y in 0..layer_height.min(base_height.saturating_sub(y)) {
+		for layer_x in 0..layer_width.min(base_width.saturating_sub(x)) {
+			let mut above = layer.get_pixel(layer_x, layer_y);
+			if let Some(tint) = tint {
+				for channel in 0..4 {
+					above[channel] = (above[channel] as u32 * tint[channel] as u32 / 255) as u8;
+				}
+			};
+			above[3] = (above[3] as f32 * opacity).round() as u8;
+			if above[3] == 0 {
+				continue;
+			};
+			let mut below = base.get_pixel(x + layer_x, y + layer_y);
+			if mode != BlendMode::Normal {
+				// Where the image below is transparent the layer keeps its own colors, as there is nothing to blend with.
+				let below_alpha = below[3] as f32 / 255.0;
+				for channel in 0..3 {
+					let below_color = below[channel] as f32 / 255.0;
+					let above_color = above[channel] as f32 / 255.0;
+					let blended = (1.0 - below_alpha) * above_color
+						+ below_alpha * mode.blend_channel(below_color, above_color);
+					above[channel] = (blended * 255.0).round() as u8;
+				}
+			};
+			below.blend(&above);
+			base.put_pixel(x + layer_x, y + layer_y, below);
+		}
+	}
+}
+
+///Fades the image out towards its edges, over the given number of pixels from each one, so it blends into whatever it overlaps.
+pub fn feather_edges(
+	img: &image::DynamicImage,
+	west: u32,
+	east: u32,
+	north: u32,
+	south: u32,
+) -> image::DynamicImage {
+	let mut feathered = img.clone();
+	let (width, height) = img.dimensions();
+	// Goes from almost transparent right at the edge to fully opaque past the feather width.
+	let ramp = |distance: u32, feather: u32| -> f32 {
+		if distance >= feather {
+			1.0
+		} else {
+			(distance + 1) as f32 / (feather + 1) as f32
+		}
+	};
+	for y in 0..height {
+		for x in 0..width {
+			let weight = ramp(x, west)
+				* ramp(width - 1 - x, east)
+				* ramp(y, north)
+				* ramp(height - 1 - y, south);
+			if weight >= 1.0 {
+				continue;
+			};
+			let mut pixel = img.get_pixel(x, y);
+			pixel[3] = (pixel[3] as f32 * weight).round() as u8;
+			feathered.put_pixel(x, y, pixel);
+		}
+	}
+	feathered
+}
@@ -0,0 +1,188 @@
+use anyhow::bail;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::config;
+
+/*
+	* * INCREMENTAL BUILDS
+	* Each output remembers a hash of everything that went into it: the input, the resolved config, every extra source file and the
+	* cutter executable itself, so a rebuilt cutter builds everything again. If none of it changed, and the files written last time are still there untouched, the build is skipped.
+	*/
+
+pub const CACHE_FILE: &str = "icon-cutter.cache";
+
+///64-bit FNV-1a. Unlike the standard library's hasher, it gives the same results on every machine and compiler version.
+struct Fnv64(u64);
+
+impl Fnv64 {
+	fn new() -> Fnv64 {
+		Fnv64(0xcbf2_9ce4_8422_2325)
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes.iter() {
+			self.0 ^= *byte as u64;
+			self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+		}
+	}
+
+	///Writes the length before the bytes, so neighboring sections can't be mistaken for one another.
+	fn write_section(&mut self, bytes: &[u8]) {
+		self.write(&(bytes.len() as u64).to_le_bytes());
+		self.write(bytes);
+	}
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+	let mut hasher = Fnv64::new();
+	hasher.write(bytes);
+	hasher.0
+}
+
+///Hashes everything the output depends on.
+pub fn input_hash(input: &[u8], prefs: &config::PrefHolder) -> Result<u64> {
+	let mut hasher = Fnv64::new();
+	// The version number rarely changes along with the cutting logic, the executable always does.
+	let executable = match std::env::current_exe().and_then(std::fs::read) {
+		Ok(thing) => thing,
+		Err(e) => bail!(
+			"Unable to read the cutter itself, to tell whether it changed since the last build: {}. Set incremental to false to build anyway.",
+			e
+		),
+	};
+	hasher.write_section(&executable);
+	hasher.write_section(input);
+	// Options that only decide whether to build at all are left out.
+	let resolved_prefs = config::PrefHolder {
+		force: false,
+		overwrite: false,
+		..prefs.clone()
+	};
+	hasher.write_section(format!("{:?}", resolved_prefs).as_bytes());
+	let mut source_files: Vec<&String> = prefs
+		.all_sources()
+		.into_iter()
+		.filter_map(|source| source.file.as_ref())
+		.collect();
+	source_files.sort();
+	source_files.dedup();
+	for path in source_files.into_iter() {
+		match std::fs::read(path) {
+			Ok(bytes) => hasher.write_section(&bytes),
+			Err(e) => bail!("Unable to read source file \"{}\": {}", path, e),
+		};
+	}
+	Ok(hasher.0)
+}
+
+///What was written for each output the last time it was built.
+struct CacheEntry {
+	input_hash: u64,
+	///Every file written, with a hash of its contents.
+	files: Vec<(String, u64)>,
+}
+
+///The hashes of every output built into a folder, kept in a text file of its own. One line per output:
+///the output name, the input hash, then the name and hash of each file written, all separated by tabs.
+pub struct BuildCache {
+	path: PathBuf,
+	entries: BTreeMap<String, CacheEntry>,
+}
+
+impl BuildCache {
+	///Loads the cache of the given output folder. Missing or broken caches are treated as empty, so everything gets built.
+	pub fn load(output_dir: &Path) -> BuildCache {
+		let path = output_dir.join(CACHE_FILE);
+		let mut entries = BTreeMap::new();
+		let text = std::fs::read_to_string(&path).unwrap_or_default();
+		for line in text.lines() {
+			let fields: Vec<&str> = line.split('\t').collect();
+			if fields.len() < 2 {
+				continue;
+			};
+			let input_hash = match u64::from_str_radix(fields[1], 16) {
+				Ok(thing) => thing,
+				Err(_) => continue,
+			};
+			// Each file written is a name followed by a hash.
+			let file_fields = fields[2..].chunks_exact(2);
+			if !file_fields.remainder().is_empty() {
+				continue;
+			};
+			let files: Option<Vec<(String, u64)>> = file_fields
+				.map(|file| {
+					u64::from_str_radix(file[1], 16)
+						.ok()
+						.map(|file_hash| (file[0].to_string(), file_hash))
+				})
+				.collect();
+			if let Some(files) = files {
+				entries.insert(fields[0].to_string(), CacheEntry { input_hash, files });
+			};
+		}
+		BuildCache { path, entries }
+	}
+
+	///Whether the output was already built out of the same inputs, with every file written still as it was left.
+	pub fn is_up_to_date(&self, output_name: &str, input_hash: u64) -> bool {
+		let entry = match self.entries.get(output_name) {
+			Some(thing) => thing,
+			None => return false,
+		};
+		entry.input_hash == input_hash
+			&& !entry.files.is_empty()
+			&& entry.files.iter().all(|(file_name, file_hash)| {
+				matches!(std::fs::read(file_name), Ok(bytes) if hash_bytes(&bytes) == *file_hash)
+			})
+	}
+
+	///Whether the file was written by a build, and left untouched since.
+	pub fn wrote(&self, file_name: &str) -> bool {
+		let bytes = match std::fs::read(file_name) {
+			Ok(thing) => thing,
+			Err(_) => return false,
+		};
+		let file_hash = hash_bytes(&bytes);
+		self.entries
+			.values()
+			.flat_map(|entry| entry.files.iter())
+			.any(|(written_name, written_hash)| {
+				written_name == file_name && *written_hash == file_hash
+			})
+	}
+
+	pub fn record(
+		&mut self,
+		output_name: &str,
+		input_hash: u64,
+		file_names: &[String],
+	) -> Result<()> {
+		let mut files = vec![];
+		for file_name in file_names.iter() {
+			let bytes = std::fs::read(file_name)?;
+			files.push((file_name.clone(), hash_bytes(&bytes)));
+		}
+		self.entries
+			.insert(output_name.to_string(), CacheEntry { input_hash, files });
+		Ok(())
+	}
+
+	pub fn save(&self) -> Result<()> {
+		let mut text = String::new();
+		for (output_name, entry) in self.entries.iter() {
+			text.push_str(&format!("{}\t{:016x}", output_name, entry.input_hash));
+			for (file_name, file_hash) in entry.files.iter() {
+				text.push_str(&format!("\t{}\t{:016x}", file_name, file_hash));
+			}
+			text.push('\n');
+		}
+		if let Some(parent) = self.path.parent() {
+			std::fs::create_dir_all(parent)?;
+		};
+		std::fs::write(&self.path, text)?;
+		Ok(())
+	}
+}
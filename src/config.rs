@@ -1,23 +1,345 @@
 use anyhow::bail;
 use anyhow::Result;
 use image::imageops;
+use image::GenericImageView;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 use yaml_rust::YamlLoader;
 
+use super::aseprite;
+use super::blend;
 use super::glob;
+use super::helpers;
+use super::hex;
+use super::input;
+use super::isometric;
+use super::nine_slice;
+use super::palette;
+use super::seams;
+use super::terminal;
+
+///Where to find an icon (or one of its corners) in the input image.
+#[derive(Clone, PartialEq, Debug)]
+pub enum IconPosition {
+	///Position in icon-state steps, as they would appear in the dmi file, counting left to right and then top to bottom.
+	Index(u32),
+	///Column and row of the cell in the icon grid. Unlike the index this doesn't shift if the image is widened.
+	Cell { column: u32, row: u32 },
+	///Explicit rectangle, in pixel coordinates. If width and height are unset, those of the piece being cut are used.
+	Rect {
+		x: u32,
+		y: u32,
+		w: Option<u32>,
+		h: Option<u32>,
+	},
+}
+
+impl Default for IconPosition {
+	fn default() -> Self {
+		IconPosition::Index(0)
+	}
+}
+
+///An icon position, along with which part of the input to read it from.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct IconSource {
+	pub position: IconPosition,
+	///Name of the layer to read from, for layered inputs. If unset the flattened image is used.
+	pub layer: Option<String>,
+	///Path of the image to read from. If unset the input file is used.
+	pub file: Option<String>,
+	///Layers stacked over this one, in order.
+	pub overlays: Vec<IconOverlay>,
+}
+
+///A layer drawn over an icon or corner, and how to blend it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct IconOverlay {
+	pub source: IconSource,
+	pub blend_mode: blend::BlendMode,
+	///From 0 (invisible) to 1 (fully opaque).
+	pub opacity: f32,
+	///Color the overlay is multiplied by, if any.
+	pub tint: Option<image::Rgba<u8>>,
+}
+
+impl IconOverlay {
+	pub fn draw(
+		&self,
+		base: &mut image::DynamicImage,
+		overlay: &image::DynamicImage,
+		x: u32,
+		y: u32,
+	) {
+		blend::composite(
+			base,
+			overlay,
+			x,
+			y,
+			self.blend_mode,
+			self.opacity,
+			self.tint,
+		);
+	}
+}
+
+///A decoded input image, along with what's needed to resolve icon positions within it.
+pub struct SourceSheet {
+	pub sheet: input::InputSheet,
+	///Settings used to resolve positions. Animated inputs hold a single frame per position, so they use a copy with frames_per_state set to 1.
+	pub layout: PrefHolder,
+	pub width_in_frames: u32,
+	pub max_total_frames: u32,
+}
+
+impl SourceSheet {
+	///Crops the given frame of a whole icon, such as a prefab, out of the sheet.
+	pub fn crop_icon(
+		&self,
+		var_name: &str,
+		source: &IconSource,
+		frame: u32,
+	) -> Result<image::DynamicImage> {
+		let (img, _) = self.sheet.frame_source(source.layer.as_deref(), frame)?;
+		crop_checked(img, var_name, self.icon_rect(var_name, source, frame)?)
+	}
+
+	///Returns the rectangle (x, y, width, height) the given frame of a whole icon is cropped from.
+	///Frames of animated inputs are images of their own, so their rectangles are within that frame's image.
+	pub fn icon_rect(
+		&self,
+		var_name: &str,
+		source: &IconSource,
+		frame: u32,
+	) -> Result<(u32, u32, u32, u32)> {
+		let (_, frame_offset) = self.sheet.frame_source(source.layer.as_deref(), frame)?;
+		self.layout.get_icon_params(
+			var_name,
+			&source.position,
+			frame_offset,
+			self.width_in_frames,
+			self.max_total_frames,
+		)
+	}
+
+	///Returns the rectangle (x, y, width, height) the given frame of a corner is cropped from.
+	///Isometric corners and hex wedges are cut out of the whole tile, so theirs is that of the tile.
+	pub fn corner_rect(
+		&self,
+		var_name: &str,
+		corner_dir: u8,
+		source: &IconSource,
+		frame: u32,
+	) -> Result<(u32, u32, u32, u32)> {
+		match self.layout.smoothing_mode {
+			SmoothingMode::Quadrants => {
+				let (_, frame_offset) = self.sheet.frame_source(source.layer.as_deref(), frame)?;
+				self.layout.get_corner_params(
+					var_name,
+					corner_dir,
+					&source.position,
+					frame_offset,
+					self.width_in_frames,
+					self.max_total_frames,
+				)
+			}
+			SmoothingMode::NineSlice { borders, .. } => {
+				let (x, y, width, height) = self.icon_rect(var_name, source, frame)?;
+				let (piece_x, piece_y, piece_width, piece_height) =
+					slice_piece_rect(var_name, corner_dir, width, height, &borders)?;
+				Ok((x + piece_x, y + piece_y, piece_width, piece_height))
+			}
+			_ => self.icon_rect(var_name, source, frame),
+		}
+	}
+
+	///Crops the given frame of a corner out of the sheet.
+	pub fn crop_corner(
+		&self,
+		var_name: &str,
+		corner_dir: u8,
+		source: &IconSource,
+		frame: u32,
+	) -> Result<image::DynamicImage> {
+		match self.layout.smoothing_mode {
+			SmoothingMode::Quadrants => (),
+			// Isometric corners and hex wedges overlap each other's rectangles, so the whole tile is cut and the rest cleared out of it.
+			SmoothingMode::Isometric {
+				tile_top,
+				tile_height,
+			} => {
+				let icon_img = self.crop_icon(var_name, source, frame)?;
+				return Ok(isometric::mask_corner(
+					&icon_img,
+					corner_dir,
+					tile_top,
+					tile_height,
+				));
+			}
+			SmoothingMode::Hex { flat_top } => {
+				let icon_img = self.crop_icon(var_name, source, frame)?;
+				return Ok(hex::mask_wedge(&icon_img, corner_dir, flat_top));
+			}
+			SmoothingMode::NineSlice { borders, .. } => {
+				let icon_img = self.crop_icon(var_name, source, frame)?;
+				let (width, height) = icon_img.dimensions();
+				let (x, y, width, height) =
+					slice_piece_rect(var_name, corner_dir, width, height, &borders)?;
+				return Ok(icon_img.crop_imm(x, y, width, height));
+			}
+		};
+		let (img, _) = self.sheet.frame_source(source.layer.as_deref(), frame)?;
+		crop_checked(
+			img,
+			var_name,
+			self.corner_rect(var_name, corner_dir, source, frame)?,
+		)
+	}
+}
+
+///Returns the rectangle (x, y, width, height) the given nine-slice piece takes in an icon of the given size, failing if it's too small for the borders.
+fn slice_piece_rect(
+	var_name: &str,
+	corner_dir: u8,
+	width: u32,
+	height: u32,
+	borders: &nine_slice::SliceBorders,
+) -> Result<(u32, u32, u32, u32)> {
+	if width < borders.west + borders.east || height < borders.north + borders.south {
+		bail!(
+			"{} is {}x{} pixels, too small for the slice borders ({:?})",
+			var_name,
+			width,
+			height,
+			borders
+		);
+	};
+	Ok(nine_slice::piece_rect(corner_dir, width, height, borders))
+}
+
+///How the junctions of an icon are changed for each of its directions, out of the south-facing ones.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DirStrategy {
+	///Rotated along with the view, by 90 degrees per cardinal direction and 45 per diagonal one.
+	Rotate,
+	///Mirrored west to east.
+	MirrorHorizontal,
+	///Mirrored north to south.
+	MirrorVertical,
+	///Mirrored across the diagonal line of the direction. Only for the diagonal directions.
+	MirrorDiagonal,
+	///Each junction moved to another one. Holds, for each position in glob::ADJ_CLOCKWISE, the position it is moved to.
+	Permutation([usize; 8]),
+}
+
+impl DirStrategy {
+	pub fn offset_signature(&self, icon_signature: u8, byond_dir: u8) -> Result<u8> {
+		Ok(match self {
+			DirStrategy::Rotate => helpers::dir_offset_signature(icon_signature, byond_dir)?,
+			DirStrategy::MirrorHorizontal => helpers::mirror_signature(icon_signature, 0),
+			DirStrategy::MirrorVertical => helpers::mirror_signature(icon_signature, 2),
+			DirStrategy::MirrorDiagonal => {
+				helpers::mirror_signature_diagonally(icon_signature, byond_dir)?
+			}
+			DirStrategy::Permutation(permutation) => {
+				helpers::permute_signature(icon_signature, permutation)
+			}
+		})
+	}
+}
+
+///How tiles are split into corners and put back together.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum SmoothingMode {
+	///Square top-down tiles, split into four quadrants by the start and end offsets.
+	#[default]
+	Quadrants,
+	///Diamond-shaped tiles, split into four smaller diamonds. Offsets are in pixels from the top of the icon.
+	Isometric { tile_top: u32, tile_height: u32 },
+	///Hexagonal tiles filling the icon, split into six wedges. Pointy-top unless flat_top is set.
+	Hex { flat_top: bool },
+	///Square tiles cut into a 3x3 grid, whose edges and center stretch (or tile, if set) to fit the output size.
+	NineSlice {
+		borders: nine_slice::SliceBorders,
+		tile: bool,
+	},
+}
+
+impl SmoothingMode {
+	///The corners (or wedges) each tile is cut into.
+	pub fn corner_dirs(&self) -> &'static [u8] {
+		match self {
+			SmoothingMode::Hex { .. } => &glob::HEX_WEDGES,
+			SmoothingMode::NineSlice { .. } => &glob::NINE_SLICE_PIECES,
+			_ => &glob::CORNER_DIRS,
+		}
+	}
+
+	///Whether the given corner is ever of the given type. Nine-slice edges are only open or closed, and the center always the same.
+	pub fn has_corner_type(&self, corner_dir: u8, corner_type: u8) -> bool {
+		match self {
+			SmoothingMode::NineSlice { .. } => nine_slice::piece_has_type(corner_dir, corner_type),
+			_ => true,
+		}
+	}
+
+	///Where the given corner goes in a tile of the given size. Corners that are whole tiles with the rest cleared go at its origin.
+	pub fn corner_origin(&self, corner_dir: u8, width: u32, height: u32) -> (u32, u32) {
+		match self {
+			SmoothingMode::NineSlice { borders, .. } => {
+				let (x, y, _, _) = nine_slice::piece_rect(corner_dir, width, height, borders);
+				(x, y)
+			}
+			_ => (0, 0),
+		}
+	}
+
+	///Which type of the given corner to use, given the neighbors in the signature.
+	pub fn corner_type(&self, corner_dir: u8, icon_signature: u8) -> u8 {
+		match self {
+			SmoothingMode::Hex { .. } => hex::wedge_type(corner_dir, icon_signature),
+			SmoothingMode::NineSlice { .. } => nine_slice::piece_type(corner_dir, icon_signature),
+			_ => helpers::smooth_dir_to_corner_type(corner_dir, icon_signature),
+		}
+	}
+}
+
+///corners -> corner_dir -> corner_type -> frames
+pub type CornerMap = BTreeMap<u8, BTreeMap<u8, Vec<image::DynamicImage>>>;
+
+///Everything cut out of the input images, ready to be assembled into icon states.
+#[derive(Clone, Debug, Default)]
+pub struct CutPieces {
+	pub corners: CornerMap,
+	///Corners of the directions with a corner set of their own, by BYOND dir.
+	pub dir_corners: BTreeMap<u8, CornerMap>,
+	///prefabs -> junction signature -> frames
+	pub prefabs: BTreeMap<u8, Vec<image::DynamicImage>>,
+	///Frames of each state overlay, in order.
+	pub state_overlays: Vec<Vec<image::DynamicImage>>,
+}
 
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct PrefHolder {
 	pub file_to_open: Option<String>,
 	pub output_name: Option<String>,
+	///Folder the outputs are written into, if not the current one.
+	pub output_dir: Option<String>,
+	///Whether each output goes into the same folders, under output_dir, as its input.
+	pub mirror_input_dirs: bool,
+	///Whether existing files can be written over, even if they weren't left by the last build.
+	pub overwrite: bool,
 	pub base_icon_state: Option<String>,
 
 	pub icon_size_x: u32,
 	pub icon_size_y: u32,
 
+	pub sheet_margin: u32,
+	pub sheet_spacing: u32,
+
 	pub west_start: u32,
 	pub west_step: u32,
 	pub east_start: u32,
@@ -33,42 +355,87 @@ pub struct PrefHolder {
 	pub output_icon_size_y: u32,
 	pub output_north_start: u32,
 	pub output_south_start: u32,
+	///Order the four corners are drawn in, the last one on top where they overlap.
+	pub corner_draw_order: Vec<u8>,
+	///How many pixels of the overlap each corner fades over, into those drawn before it.
+	pub seam_feather: u32,
+
+	pub smoothing_mode: SmoothingMode,
 
 	pub frames_per_state: u32,
 	pub delay: Option<Vec<f32>>,
 
 	pub produce_corners: bool,
+	///Whether to save every produced icon state in a labeled grid. See contact_sheet::save_contact_sheet.
+	pub produce_contact_sheet: bool,
+	///Whether to compare the pixels where corners meet, and report those that don't line up.
+	pub check_seams: bool,
+	///How much each color channel can differ across a seam before it's reported.
+	pub seam_tolerance: u8,
+	///Map to draw with the produced icons, if any. See preview::load_mask.
+	pub preview: Option<String>,
+	///What to print to the terminal once the icons are built, if anything.
+	pub terminal_preview: Option<terminal::TerminalPreview>,
+	///Whether to skip outputs whose inputs didn't change since they were last built. See cache::BuildCache.
+	pub incremental: bool,
+	///Whether to build outputs even if they are up to date.
+	pub force: bool,
+	///Whether to only explain how the input would be cut, without writing anything. See explain::explain.
+	pub dry_run: bool,
 	pub produce_dirs: bool,
+	pub produce_diagonal_dirs: bool,
+	///How the junctions are changed for each direction produced.
+	pub dir_strategies: BTreeMap<u8, DirStrategy>,
+	///Corners replaced for specific directions, by BYOND dir. The rest are the same as the south-facing ones.
+	pub dir_sources: BTreeMap<u8, Vec<(String, IconSource)>>,
+
+	pub aseprite_tag: Option<String>,
+
+	pub prefabs: Option<BTreeMap<u8, IconSource>>,
+	pub prefab_overlays: Option<BTreeMap<u8, Vec<IconOverlay>>>,
+	pub state_overlays: Vec<IconOverlay>,
+
+	pub palettes: Vec<palette::Palette>,
+	pub palette_states: bool,
+
+	pub indexed_output: bool,
+	pub max_colors: Option<u32>,
 
-	pub prefabs: Option<HashMap<u8, u32>>,
-	pub prefab_overlays: Option<HashMap<u8, Vec<u32>>>,
+	pub collapse_duplicates: bool,
 
 	pub dmi_version: String,
 
-	pub se_convex: u32,
-	pub nw_convex: u32,
-	pub ne_convex: u32,
-	pub sw_convex: u32,
+	pub se_convex: IconSource,
+	pub nw_convex: IconSource,
+	pub ne_convex: IconSource,
+	pub sw_convex: IconSource,
 
-	pub se_concave: u32,
-	pub nw_concave: u32,
-	pub ne_concave: u32,
-	pub sw_concave: u32,
+	pub se_concave: IconSource,
+	pub nw_concave: IconSource,
+	pub ne_concave: IconSource,
+	pub sw_concave: IconSource,
 
-	pub se_horizontal: u32,
-	pub nw_horizontal: u32,
-	pub ne_horizontal: u32,
-	pub sw_horizontal: u32,
+	pub se_horizontal: IconSource,
+	pub nw_horizontal: IconSource,
+	pub ne_horizontal: IconSource,
+	pub sw_horizontal: IconSource,
 
-	pub se_vertical: u32,
-	pub nw_vertical: u32,
-	pub ne_vertical: u32,
-	pub sw_vertical: u32,
+	pub se_vertical: IconSource,
+	pub nw_vertical: IconSource,
+	pub ne_vertical: IconSource,
+	pub sw_vertical: IconSource,
 
-	pub se_flat: Option<u32>,
-	pub nw_flat: Option<u32>,
-	pub ne_flat: Option<u32>,
-	pub sw_flat: Option<u32>,
+	pub se_flat: Option<IconSource>,
+	pub nw_flat: Option<IconSource>,
+	pub ne_flat: Option<IconSource>,
+	pub sw_flat: Option<IconSource>,
+
+	///Sources of the hex wedges, only used in hex mode.
+	pub hex_convex: IconSource,
+	pub hex_flat: IconSource,
+	///The junction_a icon connects across the first, third and fifth edges, and the junction_b icon across the other three.
+	pub hex_junction_a: IconSource,
+	pub hex_junction_b: IconSource,
 
 	pub is_diagonal: bool,
 }
@@ -78,67 +445,82 @@ impl PrefHolder {
 		&self,
 		input: std::io::Cursor<Vec<u8>>,
 		file_name: &str,
-	) -> Result<(
-		HashMap<u8, HashMap<u8, Vec<image::DynamicImage>>>,
-		HashMap<u8, Vec<image::DynamicImage>>,
-	)> {
-		let img = image::load(input, image::ImageFormat::Png)?;
-
-		let img_dimensions = match &img {
-			image::DynamicImage::ImageLuma8(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageLumaA8(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageRgb8(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageRgba8(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageBgr8(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageBgra8(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageLuma16(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageLumaA16(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageRgb16(inner_img) => inner_img.dimensions(),
-			image::DynamicImage::ImageRgba16(inner_img) => inner_img.dimensions(),
-		};
-
-		let width_in_frames = img_dimensions.0 / self.icon_size_x;
-		let height_in_frames = img_dimensions.1 / self.icon_size_y;
-
-		let max_total_frames = width_in_frames * height_in_frames;
+		output_name: &str,
+	) -> Result<CutPieces> {
+		let (main_sheet, other_sheets) = self.load_source_sheets(input.into_inner(), file_name)?;
+		let sheet_of = |source: &IconSource| -> &SourceSheet {
+			match &source.file {
+				Some(path) => &other_sheets[path],
+				None => &main_sheet,
+			}
+		};
+		// Crops the source and draws its overlays over it.
+		let crop_layered_corner = |var_name: &str,
+		                           corner_dir: u8,
+		                           source: &IconSource,
+		                           frame: u32|
+		 -> Result<image::DynamicImage> {
+			let mut corner_img =
+				sheet_of(source).crop_corner(var_name, corner_dir, source, frame)?;
+			for overlay in source.overlays.iter() {
+				let overlay_img = sheet_of(&overlay.source).crop_corner(
+					&format!("{} overlay", var_name),
+					corner_dir,
+					&overlay.source,
+					frame,
+				)?;
+				overlay.draw(&mut corner_img, &overlay_img, 0, 0);
+			}
+			Ok(corner_img)
+		};
+		let crop_layered_icon =
+			|var_name: &str, source: &IconSource, frame: u32| -> Result<image::DynamicImage> {
+				let mut icon_img = sheet_of(source).crop_icon(var_name, source, frame)?;
+				for overlay in source.overlays.iter() {
+					let overlay_img = sheet_of(&overlay.source).crop_icon(
+						&format!("{} overlay", var_name),
+						&overlay.source,
+						frame,
+					)?;
+					overlay.draw(&mut icon_img, &overlay_img, 0, 0);
+				}
+				Ok(icon_img)
+			};
 
 		// Index defined by glob::CORNER_DIRS
 		// corners -> corner_dir -> corner_type -> frames
 
-		let corner_types: &[u8];
-		let corners_length;
-		if self.is_diagonal {
-			corner_types = &glob::CORNER_TYPES_DIAGONAL;
-			corners_length = glob::CORNER_TYPES_DIAGONAL.len() as u32;
-		} else {
-			corner_types = &glob::CORNER_TYPES_CARDINAL;
-			corners_length = glob::CORNER_TYPES_CARDINAL.len() as u32;
-		};
+		let corner_types = self.corner_types();
+		let corners_length = corner_types.len() as u32;
 
-		let mut corners: HashMap<u8, HashMap<u8, Vec<image::DynamicImage>>> = HashMap::new();
-		for corner_dir in glob::CORNER_DIRS.iter() {
-			corners.insert(*corner_dir, HashMap::new());
-			for corner_type in corner_types.iter() {
-				let dir_map = corners.get_mut(corner_dir).unwrap();
-				dir_map.insert(*corner_type, vec![]);
-				for frame_offset in 0..self.frames_per_state {
-					let frame_vec = dir_map.get_mut(corner_type).unwrap();
-					let corner_params = self.get_corner_params(
-						*corner_dir,
-						*corner_type,
-						frame_offset,
-						width_in_frames,
-						max_total_frames,
-					)?;
-					let corner_img = img.crop_imm(
-						corner_params.0,
-						corner_params.1,
-						corner_params.2,
-						corner_params.3,
-					);
-					frame_vec.push(corner_img);
+		let cut_corners = |prefs: &PrefHolder| -> Result<CornerMap> {
+			let mut corners: CornerMap = BTreeMap::new();
+			for corner_dir in prefs.smoothing_mode.corner_dirs().iter() {
+				corners.insert(*corner_dir, BTreeMap::new());
+				for corner_type in corner_types.iter() {
+					if !prefs
+						.smoothing_mode
+						.has_corner_type(*corner_dir, *corner_type)
+					{
+						continue;
+					};
+					let dir_map = corners.get_mut(corner_dir).unwrap();
+					dir_map.insert(*corner_type, vec![]);
+					for frame in 0..prefs.frames_per_state {
+						let frame_vec = dir_map.get_mut(corner_type).unwrap();
+						let (var_name, source) =
+							prefs.get_corner_source(*corner_dir, *corner_type)?;
+						let corner_img = crop_layered_corner(var_name, *corner_dir, source, frame)?;
+						frame_vec.push(corner_img);
+					}
 				}
 			}
+			Ok(corners)
+		};
+		let mut corners = cut_corners(self)?;
+		let mut dir_corners = BTreeMap::new();
+		for byond_dir in self.dir_sources.keys() {
+			dir_corners.insert(*byond_dir, cut_corners(&self.for_dir(*byond_dir)?)?);
 		}
 
 		if self.produce_corners {
@@ -149,6 +531,28 @@ impl PrefHolder {
 			let mut index = 0;
 			for corner_type in corner_types.iter() {
 				for frame in 0..self.frames_per_state {
+					if self.smoothing_mode != SmoothingMode::Quadrants {
+						// The corners may be whole tiles with everything else cleared, so they are drawn over each other.
+						for corner_dir in self.smoothing_mode.corner_dirs().iter() {
+							let frame_img = match corners[corner_dir].get(corner_type) {
+								Some(frames) => &frames[frame as usize],
+								None => continue,
+							};
+							let (x, y) = self.smoothing_mode.corner_origin(
+								*corner_dir,
+								self.icon_size_x,
+								self.icon_size_y,
+							);
+							imageops::overlay(
+								&mut corners_image,
+								frame_img,
+								index * self.icon_size_x + x,
+								y,
+							);
+						}
+						index += 1;
+						continue;
+					};
 					let frame_img = &corners
 						.get_mut(&glob::NW_INDEX)
 						.unwrap()
@@ -196,41 +600,23 @@ impl PrefHolder {
 					index += 1;
 				}
 			}
-			let output_name = match &self.output_name {
-				Some(thing) => format!("{}", thing),
-				None => format!("{}-output", file_name),
-			};
 			corners_image
 				.save(format!("{}-corners.png", output_name))
 				.unwrap();
 		};
 
-		let mut prefabs: HashMap<u8, Vec<image::DynamicImage>> = HashMap::new();
+		if self.check_seams {
+			seams::check_seams(self, &corners, output_name)?;
+		};
+
+		let mut prefabs: BTreeMap<u8, Vec<image::DynamicImage>> = BTreeMap::new();
 		match &self.prefabs {
 			Some(thing) => {
 				for (signature, location) in thing {
 					let mut frame_vector = vec![];
 					for frame in 0..self.frames_per_state {
-						let prefab_img = img.crop_imm(
-							self.icon_size_x
-								* self.icon_positition_to_x_coordinate(
-									&format!("prefab {} icon_size_x", signature),
-									*location,
-									frame,
-									width_in_frames,
-									max_total_frames,
-								)?,
-							self.icon_size_y
-								* self.icon_positition_to_y_coordinate(
-									&format!("prefab {} icon_size_y", signature),
-									*location,
-									frame,
-									width_in_frames,
-									max_total_frames,
-								)?,
-							self.icon_size_x,
-							self.icon_size_y,
-						);
+						let prefab_img =
+							crop_layered_icon(&format!("prefab {}", signature), location, frame)?;
 						frame_vector.push(prefab_img);
 					}
 					prefabs.insert(*signature, frame_vector); // End result: prefabs -> junction signature -> frame vector -> image
@@ -246,20 +632,14 @@ impl PrefHolder {
 							if frame_vector.len() as u32 != self.frames_per_state {
 								bail!("Number of prefab overlays for signature {} does not match the frames per state ({}): {}. Aborting to avoid undefined behavior.", signature, self.frames_per_state, frame_vector.len())
 							}; // Sanity check, this should never happen unless the logic above was changed.
-							let mut unoverlaid_vector = frame_vector;
 							let mut overlaid_vector = vec![];
-							for frame in 0..self.frames_per_state {
-								let prefab_image = unoverlaid_vector.remove(0);
-								let mut overlaid_prefab = prefab_image;
-								let corner_img = img.crop_imm(
-									self.icon_size_x * self.icon_positition_to_x_coordinate("prefab_overlays", location_vec[frame as usize], frame, width_in_frames, max_total_frames)?,
-									self.icon_size_y * self.icon_positition_to_y_coordinate("prefab_overlays", location_vec[frame as usize], frame, width_in_frames, max_total_frames)?,
-									self.icon_size_x,
-									self.icon_size_y,
-									);
-								imageops::overlay(&mut overlaid_prefab, &corner_img, 0, 0);
+							for (frame, mut overlaid_prefab) in frame_vector.into_iter().enumerate() {
+								for overlay in location_vec.iter() {
+									let overlay_img = crop_layered_icon("prefab_overlays", &overlay.source, frame as u32)?;
+									overlay.draw(&mut overlaid_prefab, &overlay_img, 0, 0);
+								}
 								overlaid_vector.push(overlaid_prefab);
-								};
+							}
 							prefabs.insert(*signature, overlaid_vector);
 						},
 						None => bail!("Prefab overlay defined for inexistent prefab. Signature: {}. Overlays: {:?}.", signature, location_vec)
@@ -268,148 +648,417 @@ impl PrefHolder {
 			}
 			None => (),
 		};
-		Ok((corners, prefabs))
+
+		let mut state_overlays = vec![];
+		for overlay in self.state_overlays.iter() {
+			let mut frame_vector = vec![];
+			for frame in 0..self.frames_per_state {
+				frame_vector.push(crop_layered_icon("state_overlays", &overlay.source, frame)?);
+			}
+			state_overlays.push(frame_vector);
+		}
+
+		Ok(CutPieces {
+			corners,
+			dir_corners,
+			prefabs,
+			state_overlays,
+		})
 	}
 
-	pub fn get_corner_params(
+	///The types of corner cut for each corner dir.
+	pub fn corner_types(&self) -> &'static [u8] {
+		if let SmoothingMode::Hex { .. } = self.smoothing_mode {
+			&glob::HEX_WEDGE_TYPES
+		} else if self.is_diagonal {
+			&glob::CORNER_TYPES_DIAGONAL
+		} else {
+			&glob::CORNER_TYPES_CARDINAL
+		}
+	}
+
+	///Decodes the input image, along with every other image a source is read from, by path.
+	pub fn load_source_sheets(
+		&self,
+		input: Vec<u8>,
+		file_name: &str,
+	) -> Result<(SourceSheet, HashMap<String, SourceSheet>)> {
+		let mut any_aseprite = aseprite::is_aseprite(&input);
+		let main_aseprite_tag = self.aseprite_tag_for(&input);
+		let main_sheet = self.load_source_sheet(input, main_aseprite_tag, file_name)?;
+		let mut other_sheets: HashMap<String, SourceSheet> = HashMap::new();
+		for source in self.all_sources() {
+			let path = match &source.file {
+				Some(thing) => thing,
+				None => continue,
+			};
+			if other_sheets.contains_key(path) {
+				continue;
+			};
+			let bytes = match std::fs::read(path) {
+				Ok(thing) => thing,
+				Err(e) => bail!("Unable to read source file \"{}\": {}", path, e),
+			};
+			any_aseprite |= aseprite::is_aseprite(&bytes);
+			let aseprite_tag = self.aseprite_tag_for(&bytes);
+			let source_sheet = self.load_source_sheet(bytes, aseprite_tag, path)?;
+			other_sheets.insert(path.clone(), source_sheet);
+		}
+		if self.aseprite_tag.is_some() && !any_aseprite {
+			bail!("aseprite_tag is set, but neither the input nor any source file is an Aseprite file");
+		};
+		Ok((main_sheet, other_sheets))
+	}
+
+	///The tag to read an image with. Sheets may mix Aseprite files and plain images, main input included, so it only applies to the former.
+	fn aseprite_tag_for(&self, bytes: &[u8]) -> Option<&str> {
+		if aseprite::is_aseprite(bytes) {
+			self.aseprite_tag.as_deref()
+		} else {
+			None
+		}
+	}
+
+	///Decodes an input image and works out how many icons it holds.
+	pub fn load_source_sheet(
+		&self,
+		bytes: Vec<u8>,
+		aseprite_tag: Option<&str>,
+		file_name: &str,
+	) -> Result<SourceSheet> {
+		let sheet = input::load_input(bytes, aseprite_tag)?;
+		if sheet.is_animated() && sheet.frames.len() as u32 != self.frames_per_state {
+			bail!(
+				"{} has {} animation frames, but frames_per_state is {}. They must match.",
+				file_name,
+				sheet.frames.len(),
+				self.frames_per_state
+			);
+		};
+
+		let layout = if sheet.is_animated() {
+			PrefHolder {
+				frames_per_state: 1,
+				..self.clone()
+			}
+		} else {
+			self.clone()
+		};
+
+		let img_dimensions = sheet.frames[0].dimensions();
+
+		let width_in_frames = self.frames_in_length(img_dimensions.0, self.icon_size_x);
+		let height_in_frames = self.frames_in_length(img_dimensions.1, self.icon_size_y);
+
+		let max_total_frames = width_in_frames * height_in_frames;
+
+		Ok(SourceSheet {
+			sheet,
+			layout,
+			width_in_frames,
+			max_total_frames,
+		})
+	}
+
+	///Every corner, prefab and overlay source defined in the config.
+	pub fn all_sources(&self) -> Vec<&IconSource> {
+		let mut sources = vec![
+			&self.se_convex,
+			&self.nw_convex,
+			&self.ne_convex,
+			&self.sw_convex,
+			&self.se_concave,
+			&self.nw_concave,
+			&self.ne_concave,
+			&self.sw_concave,
+			&self.se_horizontal,
+			&self.nw_horizontal,
+			&self.ne_horizontal,
+			&self.sw_horizontal,
+			&self.se_vertical,
+			&self.nw_vertical,
+			&self.ne_vertical,
+			&self.sw_vertical,
+			&self.hex_convex,
+			&self.hex_flat,
+			&self.hex_junction_a,
+			&self.hex_junction_b,
+		];
+		for flat in [&self.se_flat, &self.nw_flat, &self.ne_flat, &self.sw_flat].iter() {
+			if let Some(thing) = flat {
+				sources.push(thing);
+			};
+		}
+		if let Some(thing) = &self.prefabs {
+			sources.extend(thing.values());
+		};
+		if let Some(thing) = &self.prefab_overlays {
+			for overlay_vec in thing.values() {
+				sources.extend(overlay_vec.iter().map(|overlay| &overlay.source));
+			}
+		};
+		sources.extend(self.state_overlays.iter().map(|overlay| &overlay.source));
+		for dir_sources in self.dir_sources.values() {
+			sources.extend(dir_sources.iter().map(|(_, source)| source));
+		}
+		let overlay_sources: Vec<&IconSource> = sources
+			.iter()
+			.flat_map(|source| source.overlays.iter().map(|overlay| &overlay.source))
+			.collect();
+		sources.extend(overlay_sources);
+		sources
+	}
+
+	///Returns a copy of these settings with the corners of the given direction, if it has any of its own, replacing the base ones.
+	pub fn for_dir(&self, byond_dir: u8) -> Result<PrefHolder> {
+		let mut dir_prefs = self.clone();
+		if let Some(dir_sources) = self.dir_sources.get(&byond_dir) {
+			for (var_name, source) in dir_sources.iter() {
+				dir_prefs.set_corner_source(var_name, source.clone())?;
+			}
+		};
+		Ok(dir_prefs)
+	}
+
+	pub fn set_corner_source(&mut self, var_name: &str, source: IconSource) -> Result<()> {
+		match var_name {
+			"ne_convex" => self.ne_convex = source,
+			"ne_concave" => self.ne_concave = source,
+			"ne_horizontal" => self.ne_horizontal = source,
+			"ne_vertical" => self.ne_vertical = source,
+			"se_convex" => self.se_convex = source,
+			"se_concave" => self.se_concave = source,
+			"se_horizontal" => self.se_horizontal = source,
+			"se_vertical" => self.se_vertical = source,
+			"sw_convex" => self.sw_convex = source,
+			"sw_concave" => self.sw_concave = source,
+			"sw_horizontal" => self.sw_horizontal = source,
+			"sw_vertical" => self.sw_vertical = source,
+			"nw_convex" => self.nw_convex = source,
+			"nw_concave" => self.nw_concave = source,
+			"nw_horizontal" => self.nw_horizontal = source,
+			"nw_vertical" => self.nw_vertical = source,
+			"ne_flat" => self.ne_flat = Some(source),
+			"se_flat" => self.se_flat = Some(source),
+			"sw_flat" => self.sw_flat = Some(source),
+			"nw_flat" => self.nw_flat = Some(source),
+			"hex_convex" => self.hex_convex = source,
+			"hex_flat" => self.hex_flat = source,
+			"hex_junction_a" => self.hex_junction_a = source,
+			"hex_junction_b" => self.hex_junction_b = source,
+			_ => bail!("Unknown corner: {}", var_name),
+		};
+		Ok(())
+	}
+
+	///Returns the config name and source of the given corner.
+	pub fn get_corner_source(
 		&self,
 		corner_dir: u8,
 		corner_type: u8,
+	) -> Result<(&str, &IconSource)> {
+		// Nine-slice edges and centers are cut out of the same icons as the corners.
+		let corner_dir = match self.smoothing_mode {
+			SmoothingMode::NineSlice { .. } => nine_slice::source_corner(corner_dir),
+			_ => corner_dir,
+		};
+		if let SmoothingMode::Hex { .. } = self.smoothing_mode {
+			// Each junction icon holds every other wedge with one of its edges connected, and the other icon the rest.
+			let first_edge_connected = match corner_type {
+				glob::HEX_CONVEX => return Ok(("hex_convex", &self.hex_convex)),
+				glob::HEX_FLAT => return Ok(("hex_flat", &self.hex_flat)),
+				glob::HEX_COUNTERCLOCKWISE => corner_dir % 2 == 0,
+				glob::HEX_CLOCKWISE => corner_dir % 2 == 1,
+				_ => bail!("get_corner_source -> {} -> {}", corner_dir, corner_type),
+			};
+			if first_edge_connected {
+				return Ok(("hex_junction_a", &self.hex_junction_a));
+			} else {
+				return Ok(("hex_junction_b", &self.hex_junction_b));
+			};
+		};
+		let flat = match corner_dir {
+			glob::NE_INDEX => ("ne_flat", &self.ne_flat),
+			glob::SE_INDEX => ("se_flat", &self.se_flat),
+			glob::SW_INDEX => ("sw_flat", &self.sw_flat),
+			glob::NW_INDEX => ("nw_flat", &self.nw_flat),
+			_ => bail!("get_corner_source -> {}", corner_dir),
+		};
+		let name_and_value = match (corner_dir, corner_type) {
+			(glob::NE_INDEX, glob::CONVEX) => ("ne_convex", &self.ne_convex),
+			(glob::NE_INDEX, glob::CONCAVE) => ("ne_concave", &self.ne_concave),
+			(glob::NE_INDEX, glob::HORIZONTAL) => ("ne_horizontal", &self.ne_horizontal),
+			(glob::NE_INDEX, glob::VERTICAL) => ("ne_vertical", &self.ne_vertical),
+			(glob::SE_INDEX, glob::CONVEX) => ("se_convex", &self.se_convex),
+			(glob::SE_INDEX, glob::CONCAVE) => ("se_concave", &self.se_concave),
+			(glob::SE_INDEX, glob::HORIZONTAL) => ("se_horizontal", &self.se_horizontal),
+			(glob::SE_INDEX, glob::VERTICAL) => ("se_vertical", &self.se_vertical),
+			(glob::SW_INDEX, glob::CONVEX) => ("sw_convex", &self.sw_convex),
+			(glob::SW_INDEX, glob::CONCAVE) => ("sw_concave", &self.sw_concave),
+			(glob::SW_INDEX, glob::HORIZONTAL) => ("sw_horizontal", &self.sw_horizontal),
+			(glob::SW_INDEX, glob::VERTICAL) => ("sw_vertical", &self.sw_vertical),
+			(glob::NW_INDEX, glob::CONVEX) => ("nw_convex", &self.nw_convex),
+			(glob::NW_INDEX, glob::CONCAVE) => ("nw_concave", &self.nw_concave),
+			(glob::NW_INDEX, glob::HORIZONTAL) => ("nw_horizontal", &self.nw_horizontal),
+			(glob::NW_INDEX, glob::VERTICAL) => ("nw_vertical", &self.nw_vertical),
+			(_, glob::FLAT) => match flat.1 {
+				Some(value) => (flat.0, value),
+				None => bail!(
+					"get_corner_source -> {} -> glob::FLAT -> {:?}",
+					corner_dir,
+					flat.1
+				),
+			},
+			_ => bail!("get_corner_source -> {} -> {}", corner_dir, corner_type),
+		};
+		Ok(name_and_value)
+	}
+
+	///Returns the crop parameters (x, y, width, height) of a corner at the given position.
+	pub fn get_corner_params(
+		&self,
+		var_name: &str,
+		corner_dir: u8,
+		position: &IconPosition,
 		frame_offset: u32,
 		width_in_frames: u32,
 		max_total_frames: u32,
 	) -> Result<(u32, u32, u32, u32)> {
 		let corner_parameters = match corner_dir {
-			glob::NE_INDEX => {
-				let pos_name_and_value = match corner_type {
-					glob::CONVEX => ("ne_convex", self.ne_convex),
-					glob::CONCAVE => ("ne_concave", self.ne_concave),
-					glob::HORIZONTAL => ("ne_horizontal", self.ne_horizontal),
-					glob::VERTICAL => ("ne_vertical", self.ne_vertical),
-					glob::FLAT => {
-						let ne_flat = match self.ne_flat {
-							Some(value) => value,
-							None => bail!(
-								"get_corner_params -> NE_INDEX -> glob::FLAT -> {:?}",
-								self.ne_flat
-							),
-						};
-						("ne_flat", ne_flat)
-					}
-					_ => bail!("get_corner_params -> NE_INDEX -> {}", corner_type),
-				};
-				(
-					pos_name_and_value.0,
-					pos_name_and_value.1,
-					self.east_start,
-					self.north_start,
-					self.east_step,
-					self.north_step,
-				)
-			}
-			glob::SE_INDEX => {
-				let pos_name_and_value = match corner_type {
-					glob::CONVEX => ("se_convex", self.se_convex),
-					glob::CONCAVE => ("se_concave", self.se_concave),
-					glob::HORIZONTAL => ("se_horizontal", self.se_horizontal),
-					glob::VERTICAL => ("se_vertical", self.se_vertical),
-					glob::FLAT => {
-						let se_flat = match self.se_flat {
-							Some(value) => value,
-							None => bail!(
-								"get_corner_params -> SE_INDEX -> glob::FLAT -> {:?}",
-								self.se_flat
-							),
-						};
-						("se_flat", se_flat)
-					}
-					_ => bail!("get_corner_params -> SE_INDEX -> {}", corner_type),
-				};
-				(
-					pos_name_and_value.0,
-					pos_name_and_value.1,
-					self.east_start,
-					self.south_start,
-					self.east_step,
-					self.south_step,
-				)
-			}
-			glob::SW_INDEX => {
-				let pos_name_and_value = match corner_type {
-					glob::CONVEX => ("sw_convex", self.sw_convex),
-					glob::CONCAVE => ("sw_concave", self.sw_concave),
-					glob::HORIZONTAL => ("sw_horizontal", self.sw_horizontal),
-					glob::VERTICAL => ("sw_vertical", self.sw_vertical),
-					glob::FLAT => {
-						let sw_flat = match self.sw_flat {
-							Some(value) => value,
-							None => bail!(
-								"get_corner_params -> SW_INDEX -> glob::FLAT -> {:?}",
-								self.sw_flat
-							),
-						};
-						("sw_flat", sw_flat)
-					}
-					_ => bail!("get_corner_params -> SW_INDEX -> {}", corner_type),
-				};
-				(
-					pos_name_and_value.0,
-					pos_name_and_value.1,
-					self.west_start,
-					self.south_start,
-					self.west_step,
-					self.south_step,
-				)
-			}
-			glob::NW_INDEX => {
-				let pos_name_and_value = match corner_type {
-					glob::CONVEX => ("nw_convex", self.nw_convex),
-					glob::CONCAVE => ("nw_concave", self.nw_concave),
-					glob::HORIZONTAL => ("nw_horizontal", self.nw_horizontal),
-					glob::VERTICAL => ("nw_vertical", self.nw_vertical),
-					glob::FLAT => {
-						let nw_flat = match self.nw_flat {
-							Some(value) => value,
-							None => bail!(
-								"get_corner_params -> NW_INDEX -> glob::FLAT -> {:?}",
-								self.nw_flat
-							),
-						};
-						("nw_flat", nw_flat)
-					}
-					_ => bail!("get_corner_params -> NW_INDEX -> {}", corner_type),
-				};
-				(
-					pos_name_and_value.0,
-					pos_name_and_value.1,
-					self.west_start,
-					self.north_start,
-					self.west_step,
-					self.north_step,
-				)
-			}
+			glob::NE_INDEX => (
+				self.east_start,
+				self.north_start,
+				self.east_step,
+				self.north_step,
+			),
+			glob::SE_INDEX => (
+				self.east_start,
+				self.south_start,
+				self.east_step,
+				self.south_step,
+			),
+			glob::SW_INDEX => (
+				self.west_start,
+				self.south_start,
+				self.west_step,
+				self.south_step,
+			),
+			glob::NW_INDEX => (
+				self.west_start,
+				self.north_start,
+				self.west_step,
+				self.north_step,
+			),
 			_ => bail!("get_corner_params -> {}", corner_dir),
 		};
-		let x_coordinate = self.icon_positition_to_x_coordinate(
-			corner_parameters.0,
-			corner_parameters.1,
+		let origin = self.icon_position_to_pixels(
+			var_name,
+			position,
 			frame_offset,
 			width_in_frames,
 			max_total_frames,
 		)?;
-		let y_coordinate = self.icon_positition_to_y_coordinate(
-			corner_parameters.0,
-			corner_parameters.1,
+		match position {
+			// An explicit rectangle points straight at the corner, so the start offsets don't apply.
+			IconPosition::Rect { w, h, .. } => Ok((
+				origin.0,
+				origin.1,
+				w.unwrap_or(corner_parameters.2),
+				h.unwrap_or(corner_parameters.3),
+			)),
+			_ => Ok((
+				origin.0 + corner_parameters.0,
+				origin.1 + corner_parameters.1,
+				corner_parameters.2,
+				corner_parameters.3,
+			)),
+		}
+	}
+
+	///Returns the crop parameters (x, y, width, height) of a whole icon, such as a prefab.
+	pub fn get_icon_params(
+		&self,
+		var_name: &str,
+		position: &IconPosition,
+		frame_offset: u32,
+		width_in_frames: u32,
+		max_total_frames: u32,
+	) -> Result<(u32, u32, u32, u32)> {
+		let origin = self.icon_position_to_pixels(
+			var_name,
+			position,
 			frame_offset,
 			width_in_frames,
 			max_total_frames,
 		)?;
-		Ok((
-			self.icon_size_x * x_coordinate + corner_parameters.2,
-			self.icon_size_x * y_coordinate + corner_parameters.3,
-			corner_parameters.4,
-			corner_parameters.5,
-		))
+		match position {
+			IconPosition::Rect { w, h, .. } => Ok((
+				origin.0,
+				origin.1,
+				w.unwrap_or(self.icon_size_x),
+				h.unwrap_or(self.icon_size_y),
+			)),
+			_ => Ok((origin.0, origin.1, self.icon_size_x, self.icon_size_y)),
+		}
+	}
+
+	///Returns the pixel coordinates of the top-left corner of the icon (or rectangle) holding the given frame.
+	///Subsequent frames are read to the right of the first one.
+	pub fn icon_position_to_pixels(
+		&self,
+		var_name: &str,
+		position: &IconPosition,
+		frame_offset: u32,
+		width_in_frames: u32,
+		max_total_frames: u32,
+	) -> Result<(u32, u32)> {
+		match position {
+			IconPosition::Index(index) => {
+				let x_coordinate = self.icon_positition_to_x_coordinate(
+					var_name,
+					*index,
+					frame_offset,
+					width_in_frames,
+					max_total_frames,
+				)?;
+				let y_coordinate = self.icon_positition_to_y_coordinate(
+					var_name,
+					*index,
+					frame_offset,
+					width_in_frames,
+					max_total_frames,
+				)?;
+				Ok(self.cell_to_pixels(x_coordinate, y_coordinate))
+			}
+			IconPosition::Cell { column, row } => {
+				let frame_column = column + frame_offset;
+				if frame_column >= width_in_frames || row * width_in_frames >= max_total_frames {
+					bail!("Unlawful value for {} ({:?}), frame {} falls outside of the image, which is {} icons wide and {} tall", var_name, position, frame_offset, width_in_frames, max_total_frames / width_in_frames.max(1));
+				};
+				Ok(self.cell_to_pixels(frame_column, *row))
+			}
+			IconPosition::Rect { x, y, .. } => Ok((
+				x + (self.icon_size_x + self.sheet_spacing) * frame_offset,
+				*y,
+			)),
+		}
+	}
+
+	///How many whole icons fit in the given length of the image, taking the sheet margin and spacing into account.
+	pub fn frames_in_length(&self, length: u32, icon_size: u32) -> u32 {
+		let usable_length = match length.checked_sub(self.sheet_margin * 2) {
+			Some(thing) => thing,
+			None => return 0,
+		};
+		(usable_length + self.sheet_spacing) / (icon_size + self.sheet_spacing)
+	}
+
+	///Pixel coordinates of the top-left corner of the icon in the given column and row of the sheet.
+	pub fn cell_to_pixels(&self, column: u32, row: u32) -> (u32, u32) {
+		(
+			self.sheet_margin + column * (self.icon_size_x + self.sheet_spacing),
+			self.sheet_margin + row * (self.icon_size_y + self.sheet_spacing),
+		)
 	}
 
 	pub fn icon_positition_to_x_coordinate(
@@ -421,7 +1070,7 @@ impl PrefHolder {
 		max_total_frames: u32,
 	) -> Result<u32> {
 		let icon_position = position * self.frames_per_state + frame_offset;
-		if icon_position > max_total_frames {
+		if icon_position >= max_total_frames {
 			bail!("Unlawful value for {} ({}), larger than the maximum amount of frames this image holds ({})", var_name, position, max_total_frames);
 		};
 		Ok(icon_position % width_in_frames)
@@ -436,13 +1085,376 @@ impl PrefHolder {
 		max_total_frames: u32,
 	) -> Result<u32> {
 		let icon_position = position * self.frames_per_state + frame_offset;
-		if icon_position > max_total_frames {
+		if icon_position >= max_total_frames {
 			bail!("Unlawful value for {} ({}), larger than the maximum amount of frames this image holds ({})", var_name, position, max_total_frames);
 		};
 		Ok(icon_position / width_in_frames) // This operation rounds towards zero, truncating any fractional part of the exact result, essentially a floor() function.
 	}
 }
 
+///Crops a piece out of the image, failing instead of silently clamping if it falls outside of it.
+pub fn crop_checked(
+	img: &image::DynamicImage,
+	var_name: &str,
+	params: (u32, u32, u32, u32),
+) -> Result<image::DynamicImage> {
+	let (width, height) = img.dimensions();
+	if params.0 + params.2 > width || params.1 + params.3 > height {
+		bail!(
+			"Unlawful crop for {}: {}x{} at ({}, {}) falls outside of the {}x{} image",
+			var_name,
+			params.2,
+			params.3,
+			params.0,
+			params.1,
+			width,
+			height
+		);
+	};
+	Ok(img.crop_imm(params.0, params.1, params.2, params.3))
+}
+
+///Reads an icon position, which can be a plain index, a [column, row] pair or a {x, y, w, h} pixel rectangle.
+pub fn yaml_to_icon_position(
+	value: &yaml_rust::yaml::Yaml,
+	var_name: &str,
+) -> Result<IconPosition> {
+	if let Some(thing) = value.as_i64() {
+		return Ok(IconPosition::Index(thing as u32));
+	};
+	if let Some(thing) = value.as_vec() {
+		if thing.len() != 2 {
+			bail!(
+				"Unlawful value for {}, a cell must have exactly two entries, [column, row]: {:?}",
+				var_name,
+				value
+			);
+		};
+		return match (thing[0].as_i64(), thing[1].as_i64()) {
+			(Some(column), Some(row)) => Ok(IconPosition::Cell {
+				column: column as u32,
+				row: row as u32,
+			}),
+			_ => bail!(
+				"Unlawful value for {}, not a proper [column, row] pair: {:?}",
+				var_name,
+				value
+			),
+		};
+	};
+	if value.as_hash().is_some() {
+		return match (
+			read_some_u32_config(value, "x"),
+			read_some_u32_config(value, "y"),
+		) {
+			(Some(x), Some(y)) => Ok(IconPosition::Rect {
+				x,
+				y,
+				w: read_some_u32_config(value, "w"),
+				h: read_some_u32_config(value, "h"),
+			}),
+			_ => bail!(
+				"Unlawful value for {}, a rectangle needs at least x and y: {:?}",
+				var_name,
+				value
+			),
+		};
+	};
+	bail!(
+		"Unlawful value for {}, not a proper position: {:?}",
+		var_name,
+		value
+	)
+}
+
+///Reads an icon source: either a bare position, or a map with an optional layer and file alongside the position.
+pub fn yaml_to_icon_source(value: &yaml_rust::yaml::Yaml, var_name: &str) -> Result<IconSource> {
+	if value.as_hash().is_none() {
+		return Ok(IconSource {
+			position: yaml_to_icon_position(value, var_name)?,
+			..Default::default()
+		});
+	};
+	for (key, _) in value.as_hash().unwrap().iter() {
+		match key.as_str() {
+			Some("position") | Some("x") | Some("y") | Some("w") | Some("h") | Some("layer")
+			| Some("file") | Some("overlays") => (),
+			_ => bail!(
+				"Unknown key {:?} in the value of {}: {:?}",
+				key,
+				var_name,
+				value
+			),
+		};
+	}
+	let layer = read_some_string_config(value, "layer");
+	let file = read_some_string_config(value, "file");
+	let mut overlays = vec![];
+	if !value["overlays"].is_badvalue() {
+		let yaml_overlays = match value["overlays"].as_vec() {
+			Some(thing) => thing,
+			None => bail!(
+				"Unlawful value for the overlays of {}, not a list: {:?}",
+				var_name,
+				value["overlays"]
+			),
+		};
+		for overlay in yaml_overlays.iter() {
+			overlays.push(yaml_to_icon_overlay(
+				overlay,
+				&format!("{} overlays", var_name),
+			)?);
+		}
+	};
+	let position;
+	if !value["position"].is_badvalue() {
+		position = yaml_to_icon_position(&value["position"], var_name)?;
+	} else if !value["x"].is_badvalue() || !value["y"].is_badvalue() {
+		position = yaml_to_icon_position(value, var_name)?;
+	} else if layer.is_some() || file.is_some() {
+		position = IconPosition::Index(0);
+	} else {
+		bail!(
+			"Unlawful value for {}, no position, layer nor file defined: {:?}",
+			var_name,
+			value
+		);
+	};
+	Ok(IconSource {
+		position,
+		layer,
+		file,
+		overlays,
+	})
+}
+
+///Reads an overlay: an icon source, along with optional blend, opacity and tint values.
+pub fn yaml_to_icon_overlay(value: &yaml_rust::yaml::Yaml, var_name: &str) -> Result<IconOverlay> {
+	let mut source_value = value.clone();
+	let mut blend_mode = blend::BlendMode::Normal;
+	let mut opacity = 1.0;
+	let mut tint = None;
+	if let yaml_rust::Yaml::Hash(hash) = &mut source_value {
+		if let Some(thing) = hash.remove(&yaml_rust::Yaml::from_str("blend")) {
+			blend_mode = match thing.as_str().and_then(blend::BlendMode::from_name) {
+				Some(mode) => mode,
+				None => bail!("Unlawful blend mode for {}: {:?}. Valid ones are normal, multiply, additive and screen.", var_name, thing),
+			};
+		};
+		if let Some(thing) = hash.remove(&yaml_rust::Yaml::from_str("opacity")) {
+			opacity = match thing {
+				yaml_rust::Yaml::Real(_) => thing.as_f64().unwrap() as f32,
+				yaml_rust::Yaml::Integer(number) => number as f32,
+				_ => bail!(
+					"Unlawful opacity for {}, not a proper number: {:?}",
+					var_name,
+					thing
+				),
+			};
+			if opacity < 0.0 || opacity > 1.0 {
+				bail!(
+					"Unlawful opacity for {} ({}), must range from 0 to 1",
+					var_name,
+					opacity
+				);
+			};
+		};
+		if let Some(thing) = hash.remove(&yaml_rust::Yaml::from_str("tint")) {
+			tint = Some(yaml_to_color(&thing, var_name)?);
+		};
+	};
+	let source = yaml_to_icon_source(&source_value, var_name)?;
+	if !source.overlays.is_empty() {
+		bail!(
+			"Overlays of {} cannot have overlays of their own: {:?}",
+			var_name,
+			value
+		);
+	};
+	Ok(IconOverlay {
+		source,
+		blend_mode,
+		opacity,
+		tint,
+	})
+}
+
+///Reads a color, either as a "#rrggbb" (or "#rrggbbaa") string or as a [red, green, blue] (or [red, green, blue, alpha]) list.
+pub fn yaml_to_color(value: &yaml_rust::yaml::Yaml, var_name: &str) -> Result<image::Rgba<u8>> {
+	let channels: Option<Vec<u8>> = if let Some(thing) = value.as_str() {
+		let hex = thing.trim_start_matches('#');
+		if hex.is_ascii() && hex.len() % 2 == 0 {
+			(0..hex.len())
+				.step_by(2)
+				.map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+				.collect()
+		} else {
+			None
+		}
+	} else if let Some(thing) = value.as_vec() {
+		thing
+			.iter()
+			.map(|channel| match channel.as_i64() {
+				Some(number) if number >= 0 && number <= 255 => Some(number as u8),
+				_ => None,
+			})
+			.collect()
+	} else {
+		None
+	};
+	match channels.as_deref() {
+		Some([red, green, blue]) => Ok(image::Rgba([*red, *green, *blue, 255])),
+		Some([red, green, blue, alpha]) => Ok(image::Rgba([*red, *green, *blue, *alpha])),
+		_ => bail!(
+			"Unlawful color for {}, must be either \"#rrggbb\", \"#rrggbbaa\", [red, green, blue] or [red, green, blue, alpha]: {:?}",
+			var_name,
+			value
+		),
+	}
+}
+
+pub fn byond_dir_from_name(name: &str) -> Option<u8> {
+	match name {
+		"south" => Some(glob::BYOND_SOUTH),
+		"north" => Some(glob::BYOND_NORTH),
+		"east" => Some(glob::BYOND_EAST),
+		"west" => Some(glob::BYOND_WEST),
+		"southeast" => Some(glob::BYOND_SOUTHEAST),
+		"southwest" => Some(glob::BYOND_SOUTHWEST),
+		"northeast" => Some(glob::BYOND_NORTHEAST),
+		"northwest" => Some(glob::BYOND_NORTHWEST),
+		_ => None,
+	}
+}
+
+pub fn byond_dir_name(byond_dir: u8) -> &'static str {
+	match byond_dir {
+		glob::BYOND_SOUTH => "south",
+		glob::BYOND_NORTH => "north",
+		glob::BYOND_EAST => "east",
+		glob::BYOND_WEST => "west",
+		glob::BYOND_SOUTHEAST => "southeast",
+		glob::BYOND_SOUTHWEST => "southwest",
+		glob::BYOND_NORTHEAST => "northeast",
+		glob::BYOND_NORTHWEST => "northwest",
+		_ => "unknown",
+	}
+}
+
+///Reads how the junctions of a direction are changed: either the name of a strategy or a map of junctions to the ones they are moved to.
+pub fn yaml_to_dir_strategy(value: &yaml_rust::yaml::Yaml, byond_dir: u8) -> Result<DirStrategy> {
+	if let Some(yaml_permutation) = value.as_hash() {
+		let junction_position = |junction: &yaml_rust::yaml::Yaml| -> Result<usize> {
+			match junction.as_str() {
+				Some("n") => Ok(0),
+				Some("ne") => Ok(1),
+				Some("e") => Ok(2),
+				Some("se") => Ok(3),
+				Some("s") => Ok(4),
+				Some("sw") => Ok(5),
+				Some("w") => Ok(6),
+				Some("nw") => Ok(7),
+				_ => bail!("Unlawful junction in the dir_strategy of dir {}: {:?}. Valid ones are n, ne, e, se, s, sw, w and nw.", byond_dir, junction),
+			}
+		};
+		let mut permutation = [0, 1, 2, 3, 4, 5, 6, 7];
+		for (from, to) in yaml_permutation.iter() {
+			permutation[junction_position(from)?] = junction_position(to)?;
+		}
+		let mut sorted_permutation = permutation;
+		sorted_permutation.sort();
+		if sorted_permutation != [0, 1, 2, 3, 4, 5, 6, 7] {
+			bail!(
+				"The dir_strategy of dir {} moves more than one junction to the same place: {:?}",
+				byond_dir,
+				value
+			);
+		};
+		return Ok(DirStrategy::Permutation(permutation));
+	};
+	let strategy = match value.as_str() {
+		Some("rotate") => DirStrategy::Rotate,
+		Some("mirror_horizontal") => DirStrategy::MirrorHorizontal,
+		Some("mirror_vertical") => DirStrategy::MirrorVertical,
+		Some("mirror_diagonal") => DirStrategy::MirrorDiagonal,
+		_ => bail!("Unlawful dir_strategy for dir {}: {:?}. Must be either rotate, mirror_horizontal, mirror_vertical, mirror_diagonal or a map of junctions.", byond_dir, value),
+	};
+	if strategy == DirStrategy::MirrorDiagonal && glob::BYOND_CARDINALS.contains(&byond_dir) {
+		bail!(
+			"mirror_diagonal can only be used by diagonal directions, not dir {}",
+			byond_dir
+		);
+	};
+	Ok(strategy)
+}
+
+///Reads a palette: either a map of source colors to their replacements, or a {file, row} pair pointing at a palette image.
+pub fn yaml_to_palette(value: &yaml_rust::yaml::Yaml, name: &str) -> Result<palette::Palette> {
+	let var_name = format!("palette {}", name);
+	let yaml_colors = match value.as_hash() {
+		Some(thing) => thing,
+		None => bail!(
+			"Unlawful value for {}, must be either a map of colors or a file and row: {:?}",
+			var_name,
+			value
+		),
+	};
+	if let Some(path) = read_some_string_config(value, "file") {
+		let row = match read_some_u32_config(value, "row") {
+			Some(thing) => thing,
+			None => bail!("Undefined row for {}, which reads from {}", var_name, path),
+		};
+		let img = match image::open(&path) {
+			Ok(thing) => thing,
+			Err(e) => bail!("Unable to read palette file \"{}\": {}", path, e),
+		};
+		return palette::Palette::from_image(name, &img, row);
+	};
+	let mut colors = BTreeMap::new();
+	for (source, target) in yaml_colors.iter() {
+		let source = yaml_to_color(source, &var_name)?;
+		let target = yaml_to_color(target, &var_name)?;
+		if source[3] != 255 || target[3] != 255 {
+			bail!(
+				"Unlawful color for {}, palettes only replace red, green and blue and keep the alpha of each pixel: {:?} -> {:?}",
+				var_name,
+				source,
+				target
+			);
+		};
+		colors.insert(
+			[source[0], source[1], source[2]],
+			[target[0], target[1], target[2]],
+		);
+	}
+	Ok(palette::Palette {
+		name: name.to_string(),
+		colors,
+	})
+}
+
+pub fn read_some_source_config(
+	source: &yaml_rust::yaml::Yaml,
+	index: &str,
+) -> Result<Option<IconSource>> {
+	let config = &source[index];
+	if config.is_badvalue() {
+		return Ok(None);
+	};
+	Ok(Some(yaml_to_icon_source(config, index)?))
+}
+
+pub fn read_necessary_source_config(
+	source: &yaml_rust::yaml::Yaml,
+	index: &str,
+) -> Result<IconSource> {
+	let config = &source[index];
+	if config.is_badvalue() {
+		bail!("Undefined value for {}. This is a necessary config. Please check config.yaml in the examples folder for documentation.", index);
+	};
+	yaml_to_icon_source(config, index)
+}
+
 pub fn read_some_u32_config(source: &yaml_rust::yaml::Yaml, index: &str) -> Option<u32> {
 	let config = &source[index];
 	if config.is_badvalue() {
@@ -495,31 +1507,56 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 	let docs = YamlLoader::load_from_str(&contents).unwrap();
 	let doc = &docs[0];
 
-	let se_convex = read_necessary_u32_config(&doc, "se_convex")?;
-	let nw_convex = read_necessary_u32_config(&doc, "nw_convex")?;
-	let ne_convex = read_necessary_u32_config(&doc, "ne_convex")?;
-	let sw_convex = read_necessary_u32_config(&doc, "sw_convex")?;
-	let se_concave = read_necessary_u32_config(&doc, "se_concave")?;
-	let nw_concave = read_necessary_u32_config(&doc, "nw_concave")?;
-	let ne_concave = read_necessary_u32_config(&doc, "ne_concave")?;
-	let sw_concave = read_necessary_u32_config(&doc, "sw_concave")?;
-	let se_horizontal = read_necessary_u32_config(&doc, "se_horizontal")?;
-	let nw_horizontal = read_necessary_u32_config(&doc, "nw_horizontal")?;
-	let ne_horizontal = read_necessary_u32_config(&doc, "ne_horizontal")?;
-	let sw_horizontal = read_necessary_u32_config(&doc, "sw_horizontal")?;
-	let se_vertical = read_necessary_u32_config(&doc, "se_vertical")?;
-	let nw_vertical = read_necessary_u32_config(&doc, "nw_vertical")?;
-	let ne_vertical = read_necessary_u32_config(&doc, "ne_vertical")?;
-	let sw_vertical = read_necessary_u32_config(&doc, "sw_vertical")?;
-
-	let se_flat = read_some_u32_config(&doc, "se_flat");
-	let nw_flat = read_some_u32_config(&doc, "nw_flat");
-	let ne_flat = read_some_u32_config(&doc, "ne_flat");
-	let sw_flat = read_some_u32_config(&doc, "sw_flat");
+	// Hex tiles are cut out of their own sources, so the square corners are only needed otherwise.
+	let hex_mode = read_some_string_config(&doc, "smoothing_mode")
+		.map_or(false, |mode| mode.starts_with("hex"));
+	let read_corner_config = |index: &str| -> Result<IconSource> {
+		if hex_mode {
+			return Ok(IconSource::default());
+		};
+		read_necessary_source_config(&doc, index)
+	};
+	let read_hex_config = |index: &str| -> Result<IconSource> {
+		if !hex_mode {
+			return Ok(IconSource::default());
+		};
+		read_necessary_source_config(&doc, index)
+	};
+
+	let se_convex = read_corner_config("se_convex")?;
+	let nw_convex = read_corner_config("nw_convex")?;
+	let ne_convex = read_corner_config("ne_convex")?;
+	let sw_convex = read_corner_config("sw_convex")?;
+	let se_concave = read_corner_config("se_concave")?;
+	let nw_concave = read_corner_config("nw_concave")?;
+	let ne_concave = read_corner_config("ne_concave")?;
+	let sw_concave = read_corner_config("sw_concave")?;
+	let se_horizontal = read_corner_config("se_horizontal")?;
+	let nw_horizontal = read_corner_config("nw_horizontal")?;
+	let ne_horizontal = read_corner_config("ne_horizontal")?;
+	let sw_horizontal = read_corner_config("sw_horizontal")?;
+	let se_vertical = read_corner_config("se_vertical")?;
+	let nw_vertical = read_corner_config("nw_vertical")?;
+	let ne_vertical = read_corner_config("ne_vertical")?;
+	let sw_vertical = read_corner_config("sw_vertical")?;
+
+	let hex_convex = read_hex_config("hex_convex")?;
+	let hex_flat = read_hex_config("hex_flat")?;
+	let hex_junction_a = read_hex_config("hex_junction_a")?;
+	let hex_junction_b = read_hex_config("hex_junction_b")?;
+
+	let se_flat = read_some_source_config(&doc, "se_flat")?;
+	let nw_flat = read_some_source_config(&doc, "nw_flat")?;
+	let ne_flat = read_some_source_config(&doc, "ne_flat")?;
+	let sw_flat = read_some_source_config(&doc, "sw_flat")?;
 
 	let file_to_open = read_some_string_config(&doc, "file_to_open");
 	let output_name = read_some_string_config(&doc, "output_name");
+	let output_dir = read_some_string_config(&doc, "output_dir");
+	let mirror_input_dirs = doc["mirror_input_dirs"].as_bool().unwrap_or(false);
+	let overwrite = doc["overwrite"].as_bool().unwrap_or(false);
 	let base_icon_state = read_some_string_config(&doc, "base_icon_state");
+	let aseprite_tag = read_some_string_config(&doc, "aseprite_tag");
 
 	let icon_size_x = match read_some_u32_config(&doc, "icon_size_x") {
 		Some(thing) => {
@@ -531,6 +1568,9 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		}
 		None => glob::TILE_SIZE,
 	};
+	let sheet_margin = read_some_u32_config(&doc, "sheet_margin").unwrap_or(0);
+	let sheet_spacing = read_some_u32_config(&doc, "sheet_spacing").unwrap_or(0);
+
 	let west_start = match read_some_u32_config(&doc, "west_start") {
 		Some(thing) => {
 			if thing > icon_size_x {
@@ -747,6 +1787,82 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		None => output_north_start + north_end - north_start,
 	};
 
+	// Corners are cut past the middle of the icon and placed over each other by this much, leaving room to blend them.
+	let corner_overlap = read_some_u32_config(&doc, "corner_overlap").unwrap_or(0);
+	if corner_overlap > 0 {
+		if west_end + corner_overlap > icon_size_x
+			|| north_end + corner_overlap > icon_size_y
+			|| east_start < corner_overlap
+			|| south_start < corner_overlap
+		{
+			bail!(
+				"Unlawful value for corner_overlap ({}), the corners can't be cut past the edges of the icon",
+				corner_overlap
+			);
+		};
+		if output_east_start < corner_overlap || output_south_start < corner_overlap {
+			bail!(
+				"Unlawful value for corner_overlap ({}), larger than output_east_start ({}) or output_south_start ({})",
+				corner_overlap,
+				output_east_start,
+				output_south_start
+			);
+		};
+	};
+	let west_step = west_step + corner_overlap;
+	let east_start = east_start - corner_overlap;
+	let east_step = east_step + corner_overlap;
+	let north_step = north_step + corner_overlap;
+	let south_start = south_start - corner_overlap;
+	let south_step = south_step + corner_overlap;
+	let output_east_start = output_east_start - corner_overlap;
+	let output_south_start = output_south_start - corner_overlap;
+
+	let corner_draw_order = match doc["corner_draw_order"].as_vec() {
+		None => vec![
+			glob::NW_INDEX,
+			glob::NE_INDEX,
+			glob::SE_INDEX,
+			glob::SW_INDEX,
+		],
+		Some(yaml_order) => {
+			let mut order = vec![];
+			for yaml_corner in yaml_order.iter() {
+				let corner_dir = match yaml_corner.as_str() {
+					Some("ne") => glob::NE_INDEX,
+					Some("se") => glob::SE_INDEX,
+					Some("sw") => glob::SW_INDEX,
+					Some("nw") => glob::NW_INDEX,
+					_ => bail!("Unlawful corner in corner_draw_order: {:?}. Valid ones are ne, se, sw and nw.", yaml_corner),
+				};
+				if order.contains(&corner_dir) {
+					bail!("Corner repeated in corner_draw_order: {:?}", yaml_corner);
+				};
+				order.push(corner_dir);
+			}
+			if order.len() != glob::CORNER_DIRS.len() {
+				bail!(
+					"corner_draw_order must list each of the four corners once, read value: {:?}",
+					yaml_order
+				);
+			};
+			order
+		}
+	};
+
+	let seam_feather = read_some_u32_config(&doc, "seam_feather").unwrap_or(0);
+	// Past the overlap the corners would fade into nothing, leaving see-through seams.
+	let overlap_x = (output_west_start + west_step).saturating_sub(output_east_start);
+	let overlap_y = (output_north_start + north_step).saturating_sub(output_south_start);
+	if seam_feather > overlap_x.min(overlap_y) {
+		bail!(
+			"Unlawful value for seam_feather ({}), wider than the overlap of the corners ({} pixels horizontally and {} vertically). See corner_overlap.",
+			seam_feather,
+			overlap_x,
+			overlap_y
+		);
+	};
+
 	let frames_per_state = match read_some_u32_config(&doc, "frames_per_state") {
 		Some(thing) => {
 			if thing <= 0 {
@@ -774,7 +1890,18 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 					None => bail!("Delay config improperly set. Please look at the example files for the proper format. Contents: {:?}", doc["delay"])
 				};
 			for delay_value in yaml_delay.iter() {
-				delay_vec.push(delay_value.as_f64().unwrap() as f32);
+				// Whole numbers of ticks are read as integers.
+				let delay_ticks = match delay_value.as_f64() {
+					Some(thing) => thing,
+					None => match delay_value.as_i64() {
+						Some(thing) => thing as f64,
+						None => bail!(
+							"Unlawful delay value, must be a number of ticks: {:?}",
+							delay_value
+						),
+					},
+				};
+				delay_vec.push(delay_ticks as f32);
 			}
 			if delay_vec.len() as u32 > frames_per_state {
 				bail!(
@@ -799,6 +1926,98 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		delay = Some(delay_vec);
 	};
 
+	let smoothing_mode = match read_some_string_config(&doc, "smoothing_mode").as_deref() {
+		None | Some("quadrants") => SmoothingMode::Quadrants,
+		Some("isometric") => {
+			let tile_height = match read_some_u32_config(&doc, "iso_tile_height") {
+				Some(thing) => thing,
+				None => icon_size_x / 2,
+			};
+			if tile_height == 0 || tile_height > icon_size_y {
+				bail!(
+					"Unlawful value for iso_tile_height ({}), cannot be 0 nor larger than icon_size_y ({})",
+					tile_height,
+					icon_size_y
+				);
+			};
+			let tile_top = match read_some_u32_config(&doc, "iso_tile_top") {
+				Some(thing) => {
+					if thing + tile_height > icon_size_y {
+						bail!(
+							"Unlawful value for iso_tile_top ({}), the tile would end past icon_size_y ({}) with an iso_tile_height of {}",
+							thing,
+							icon_size_y,
+							tile_height
+						);
+					};
+					thing
+				}
+				None => icon_size_y - tile_height,
+			};
+			SmoothingMode::Isometric {
+				tile_top,
+				tile_height,
+			}
+		}
+		Some("hex_pointy") => SmoothingMode::Hex { flat_top: false },
+		Some("hex_flat") => SmoothingMode::Hex { flat_top: true },
+		Some("nine_slice") => {
+			let borders = nine_slice::SliceBorders {
+				west: read_some_u32_config(&doc, "slice_west").unwrap_or(icon_size_x / 3),
+				east: read_some_u32_config(&doc, "slice_east").unwrap_or(icon_size_x / 3),
+				north: read_some_u32_config(&doc, "slice_north").unwrap_or(icon_size_y / 3),
+				south: read_some_u32_config(&doc, "slice_south").unwrap_or(icon_size_y / 3),
+			};
+			let slice_width = borders.west + borders.east;
+			let slice_height = borders.north + borders.south;
+			if slice_width > icon_size_x.min(output_icon_size_x) {
+				bail!(
+					"Unlawful values for slice_west ({}) and slice_east ({}), together larger than icon_size_x ({}) or output_icon_size_x ({})",
+					borders.west,
+					borders.east,
+					icon_size_x,
+					output_icon_size_x
+				);
+			};
+			if slice_height > icon_size_y.min(output_icon_size_y) {
+				bail!(
+					"Unlawful values for slice_north ({}) and slice_south ({}), together larger than icon_size_y ({}) or output_icon_size_y ({})",
+					borders.north,
+					borders.south,
+					icon_size_y,
+					output_icon_size_y
+				);
+			};
+			// An empty middle can't be stretched into a wider one.
+			if (slice_width == icon_size_x && slice_width < output_icon_size_x)
+				|| (slice_height == icon_size_y && slice_height < output_icon_size_y)
+			{
+				bail!("The slice borders leave no middle to fill the larger output icon with");
+			};
+			let tile = match read_some_string_config(&doc, "slice_fill").as_deref() {
+				None | Some("stretch") => false,
+				Some("tile") => true,
+				Some(thing) => bail!(
+					"Unlawful value for slice_fill: {}. Valid ones are stretch and tile.",
+					thing
+				),
+			};
+			SmoothingMode::NineSlice { borders, tile }
+		}
+		Some(thing) => bail!(
+			"Unlawful value for smoothing_mode: {}. Valid ones are quadrants, isometric, hex_pointy, hex_flat and nine_slice.",
+			thing
+		),
+	};
+	if smoothing_mode != SmoothingMode::Quadrants {
+		if corner_overlap > 0 {
+			bail!("corner_overlap only works for the quadrants smoothing_mode");
+		};
+		if seam_feather > 0 {
+			bail!("seam_feather only works for the quadrants smoothing_mode");
+		};
+	};
+
 	let produce_corners;
 	if doc["produce_corners"].is_badvalue() {
 		produce_corners = false;
@@ -809,6 +2028,47 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		};
 	};
 
+	let produce_contact_sheet = doc["produce_contact_sheet"].as_bool().unwrap_or(false);
+
+	let check_seams = doc["check_seams"].as_bool().unwrap_or(false);
+	if check_seams && (smoothing_mode != SmoothingMode::Quadrants || corner_overlap > 0) {
+		bail!("check_seams only works for the quadrants smoothing_mode, with corners that don't overlap");
+	};
+	let seam_tolerance = match read_some_u32_config(&doc, "seam_tolerance") {
+		Some(thing) => {
+			if thing > 255 {
+				bail!(
+					"Unlawful value for seam_tolerance ({}), larger than 255",
+					thing
+				);
+			};
+			thing as u8
+		}
+		None => 0,
+	};
+
+	let preview = read_some_string_config(&doc, "preview");
+	if preview.is_some() && matches!(smoothing_mode, SmoothingMode::Hex { .. }) {
+		bail!("preview only works for the square and isometric smoothing_modes");
+	};
+	let incremental = doc["incremental"].as_bool().unwrap_or(true);
+
+	let terminal_preview = match read_some_string_config(&doc, "terminal_preview") {
+		Some(thing) => match terminal::TerminalPreview::from_name(&thing) {
+			Some(terminal_preview) => Some(terminal_preview),
+			None => bail!(
+				"Unlawful value for terminal_preview: {}. Valid ones are states and map.",
+				thing
+			),
+		},
+		None => None,
+	};
+	if terminal_preview == Some(terminal::TerminalPreview::Map)
+		&& matches!(smoothing_mode, SmoothingMode::Hex { .. })
+	{
+		bail!("terminal_preview map only works for the square and isometric smoothing_modes");
+	};
+
 	let produce_dirs;
 	if doc["produce_dirs"].is_badvalue() {
 		produce_dirs = false;
@@ -819,11 +2079,122 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		};
 	};
 
+	if let SmoothingMode::Hex { .. } = smoothing_mode {
+		if produce_dirs {
+			bail!(
+				"produce_dirs is enabled, but hex tiles can't be rotated into BYOND's directions"
+			);
+		};
+	};
+
+	let diagonal_dirs = if doc["diagonal_dirs"].is_badvalue() {
+		None
+	} else {
+		match doc["diagonal_dirs"].as_str() {
+			Some(thing) => Some(thing),
+			None => bail!(
+				"Unlawful value for diagonal_dirs ({:?}), must be either rotate or mirror",
+				doc["diagonal_dirs"]
+			),
+		}
+	};
+	let produce_diagonal_dirs = diagonal_dirs.is_some();
+	if produce_diagonal_dirs && !produce_dirs {
+		bail!("diagonal_dirs is set, but produce_dirs is not enabled");
+	};
+	let diagonal_strategy = match diagonal_dirs {
+		Some("rotate") | None => DirStrategy::Rotate,
+		Some("mirror") => DirStrategy::MirrorDiagonal,
+		Some(thing) => bail!(
+			"Unlawful value for diagonal_dirs ({}), must be either rotate or mirror",
+			thing
+		),
+	};
+
+	let mut dir_strategies: BTreeMap<u8, DirStrategy> = BTreeMap::new();
+	for byond_dir in glob::BYOND_ALL_DIRS.iter() {
+		if glob::BYOND_CARDINALS.contains(byond_dir) {
+			dir_strategies.insert(*byond_dir, DirStrategy::Rotate);
+		} else {
+			dir_strategies.insert(*byond_dir, diagonal_strategy.clone());
+		};
+	}
+	let yaml_dir_strategy = &doc["dir_strategy"];
+	if !yaml_dir_strategy.is_badvalue() {
+		if let Some(yaml_strategies) = yaml_dir_strategy.as_hash() {
+			for (dir_name, value) in yaml_strategies.iter() {
+				let byond_dir = match dir_name.as_str().and_then(byond_dir_from_name) {
+					Some(thing) => thing,
+					None => bail!("Unlawful direction in dir_strategy: {:?}. Valid ones are south, north, east, west, southeast, southwest, northeast and northwest.", dir_name),
+				};
+				dir_strategies.insert(byond_dir, yaml_to_dir_strategy(value, byond_dir)?);
+			}
+		} else {
+			// A single strategy applies to the north, east and west directions. Diagonal ones follow diagonal_dirs.
+			for byond_dir in [glob::BYOND_NORTH, glob::BYOND_EAST, glob::BYOND_WEST].iter() {
+				dir_strategies.insert(
+					*byond_dir,
+					yaml_to_dir_strategy(yaml_dir_strategy, *byond_dir)?,
+				);
+			}
+		};
+	};
+
+	let is_diagonal = se_flat != None && nw_flat != None && ne_flat != None && sw_flat != None;
+
+	let mut dir_sources: BTreeMap<u8, Vec<(String, IconSource)>> = BTreeMap::new();
+	if !doc["dir_corners"].is_badvalue() {
+		let yaml_dir_corners = match doc["dir_corners"].as_hash() {
+			Some(thing) => thing,
+			None => bail!("dir_corners defined with the wrong format. See the config.yaml in the example folder for a valid one. Read value: {:?}", doc["dir_corners"])
+		};
+		for (dir_name, yaml_corners) in yaml_dir_corners.iter() {
+			let byond_dir = match dir_name.as_str().and_then(byond_dir_from_name) {
+				Some(thing) => thing,
+				None => bail!("Unlawful direction in dir_corners: {:?}. Valid ones are south, north, east, west, southeast, southwest, northeast and northwest.", dir_name),
+			};
+			let produced = if glob::BYOND_CARDINALS.contains(&byond_dir) {
+				produce_dirs
+			} else {
+				produce_diagonal_dirs
+			};
+			if !produced {
+				bail!("dir_corners defined for {:?}, but that direction is not produced. Check produce_dirs and diagonal_dirs.", dir_name);
+			};
+			let yaml_corners = match yaml_corners.as_hash() {
+				Some(thing) => thing,
+				None => bail!(
+					"dir_corners values for {:?} improperly set: {:?}",
+					dir_name,
+					yaml_corners
+				),
+			};
+			let mut corner_sources = vec![];
+			for (var_name, value) in yaml_corners.iter() {
+				let var_name = match var_name.as_str() {
+					Some(thing) => thing,
+					None => bail!(
+						"Unlawful corner name in dir_corners for {:?}: {:?}",
+						dir_name,
+						var_name
+					),
+				};
+				if var_name.ends_with("_flat") && !is_diagonal {
+					bail!("{} defined in dir_corners for {:?}, but the base flat corners are not defined", var_name, dir_name);
+				};
+				let source =
+					yaml_to_icon_source(value, &format!("{} of {:?}", var_name, dir_name))?;
+				corner_sources.push((var_name.to_string(), source));
+			}
+			dir_sources.insert(byond_dir, corner_sources);
+		}
+	};
+
 	let prefabs;
 	if doc["prefabs"].is_badvalue() {
 		prefabs = None;
 	} else {
-		let mut prefab_map: HashMap<u8, u32> = HashMap::new();
+		let mut prefab_map: BTreeMap<u8, IconSource> = BTreeMap::new();
 		let yaml_prefabs = match doc["prefabs"].as_hash() {
 			Some(thing) => thing,
 			None => bail!("prefabs value improperly setup: {:?}", doc["prefabs"]),
@@ -833,14 +2204,7 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 				Some(thing) => thing as u8,
 				None => bail!("prefab signature value improperly: {:?}", prefab_signature),
 			};
-			let position = match position.as_i64() {
-				Some(thing) => thing as u32,
-				None => bail!(
-					"prefab value improperly set for {}: {:?}",
-					signature,
-					position
-				),
-			};
+			let position = yaml_to_icon_source(position, &format!("prefab {}", signature))?;
 			prefab_map.insert(signature, position);
 		}
 		prefabs = Some(prefab_map);
@@ -850,7 +2214,7 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 	if doc["prefab_overlays"].is_badvalue() {
 		prefab_overlays = None;
 	} else {
-		let mut overlays_map: HashMap<u8, Vec<u32>> = HashMap::new();
+		let mut overlays_map: BTreeMap<u8, Vec<IconOverlay>> = BTreeMap::new();
 		let yaml_prefab_overlays =  match doc["prefab_overlays"].as_hash() {
 			Some(thing) => thing,
 			None => bail!("prefab_overlays defined with the wrong format. See the config.yaml in the example folder for a valid one. Read value: {:?}", doc["prefab_overlays"])
@@ -870,14 +2234,10 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 			};
 			let mut overlay_vec = vec![];
 			for value in coords_list.iter() {
-				let value = match value.as_i64() {
-					Some(thing) => thing as u32,
-					None => bail!(
-						"Improper value found in prefab_overlays for signature {}: {:?}",
-						signature,
-						value
-					),
-				};
+				let value = yaml_to_icon_overlay(
+					value,
+					&format!("prefab_overlays for signature {}", signature),
+				)?;
 				overlay_vec.push(value)
 			}
 			if overlay_vec.len() == 0 {
@@ -891,14 +2251,82 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		prefab_overlays = Some(overlays_map);
 	};
 
+	let mut state_overlays = vec![];
+	if !doc["state_overlays"].is_badvalue() {
+		let yaml_state_overlays = match doc["state_overlays"].as_vec() {
+			Some(thing) => thing,
+			None => bail!("state_overlays defined with the wrong format. See the config.yaml in the example folder for a valid one. Read value: {:?}", doc["state_overlays"])
+		};
+		for value in yaml_state_overlays.iter() {
+			state_overlays.push(yaml_to_icon_overlay(value, "state_overlays")?);
+		}
+	};
+
+	let mut palettes = vec![];
+	if !doc["palettes"].is_badvalue() {
+		let yaml_palettes = match doc["palettes"].as_hash() {
+			Some(thing) => thing,
+			None => bail!("palettes defined with the wrong format. See the config.yaml in the example folder for a valid one. Read value: {:?}", doc["palettes"])
+		};
+		for (palette_name, palette_value) in yaml_palettes.iter() {
+			let palette_name = match palette_name.as_str() {
+				Some(thing) => thing,
+				None => bail!("Palette name must be a string: {:?}", palette_name),
+			};
+			palettes.push(yaml_to_palette(palette_value, palette_name)?);
+		}
+	};
+
+	let palette_states;
+	if doc["palette_states"].is_badvalue() {
+		palette_states = false;
+	} else {
+		palette_states = match doc["palette_states"].as_bool() {
+			Some(thing) => thing,
+			None => false,
+		};
+	};
+
+	let indexed_output;
+	if doc["indexed_output"].is_badvalue() {
+		indexed_output = false;
+	} else {
+		indexed_output = match doc["indexed_output"].as_bool() {
+			Some(thing) => thing,
+			None => false,
+		};
+	};
+
+	let max_colors = match read_some_u32_config(&doc, "max_colors") {
+		Some(thing) => {
+			if thing < 2 || thing > 256 {
+				bail!(
+					"Unlawful value for max_colors ({}), must range from 2 to 256",
+					thing
+				);
+			} else {
+				Some(thing)
+			}
+		}
+		None => None,
+	};
+
+	let collapse_duplicates;
+	if doc["collapse_duplicates"].is_badvalue() {
+		collapse_duplicates = false;
+	} else {
+		collapse_duplicates = match doc["collapse_duplicates"].as_bool() {
+			Some(thing) => thing,
+			None => false,
+		};
+	};
+
 	let dmi_version = match read_some_string_config(&doc, "dmi_version") {
 		Some(thing) => thing.to_string(),
 		None => "4.0".to_string(),
 	};
 
-	let is_diagonal = se_flat != None && nw_flat != None && ne_flat != None && sw_flat != None;
-
-	return Ok(PrefHolder {
+	let prefs = PrefHolder {
 		se_convex,
 		nw_convex,
 		ne_convex,
@@ -921,10 +2349,21 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		ne_flat,
 		sw_flat,
 
+		hex_convex,
+		hex_flat,
+		hex_junction_a,
+		hex_junction_b,
+
 		file_to_open,
 		output_name,
+		output_dir,
+		mirror_input_dirs,
+		overwrite,
 		base_icon_state,
 
+		sheet_margin,
+		sheet_spacing,
+
 		icon_size_x,
 		west_start,
 		west_step,
@@ -942,18 +2381,49 @@ pub fn load_configs(caller_path: String) -> Result<PrefHolder> {
 		output_icon_size_y,
 		output_north_start,
 		output_south_start,
+		corner_draw_order,
+		seam_feather,
+
+		smoothing_mode,
 
 		frames_per_state,
 		delay,
 
 		produce_corners,
+		produce_contact_sheet,
+		check_seams,
+		seam_tolerance,
+		preview,
+		terminal_preview,
+		incremental,
+		force: false,
+		dry_run: false,
 		produce_dirs,
+		produce_diagonal_dirs,
+		dir_strategies,
+		dir_sources,
+
+		aseprite_tag,
 
 		prefabs,
 		prefab_overlays,
+		state_overlays,
+
+		palettes,
+		palette_states,
+
+		indexed_output,
+		max_colors,
+
+		collapse_duplicates,
 
 		dmi_version,
 
 		is_diagonal,
-	});
+	};
+	// Catches unknown corner names in dir_corners while the config is loaded, rather than when the icons are cut.
+	for byond_dir in prefs.dir_sources.keys() {
+		prefs.for_dir(*byond_dir)?;
+	}
+	return Ok(prefs);
 }
@@ -0,0 +1,204 @@
+use anyhow::Result;
+use dmi::icon;
+use image::GenericImage;
+
+use super::config;
+use super::glob;
+use super::hex;
+
+/*
+	* * CONTACT SHEET
+	* Every produced icon state in a grid, each labeled with its signature and a glyph of the neighbors it connects to.
+	* When several dirs are produced, each gets a block of its own, headed by its name.
+	*/
+
+//3x5 pixel font. Each row is three bits, the highest one being the leftmost pixel.
+const FONT: [(char, [u8; 5]); 20] = [
+	('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+	('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+	('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+	('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+	('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+	('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+	('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+	('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+	('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+	('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+	('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+	('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+	('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+	('N', [0b101, 0b111, 0b111, 0b101, 0b101]),
+	('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+	('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+	('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+	('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+	('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+	('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+];
+const CHAR_WIDTH: u32 = 4; // Including the space after it.
+const CHAR_HEIGHT: u32 = 5;
+
+//The neighbor glyph is a 3x3 grid of cells, the tile itself in the middle.
+const GLYPH_CELL: u32 = 3;
+const GLYPH_SIZE: u32 = GLYPH_CELL * 3;
+
+const PADDING: u32 = 2;
+const LABEL_HEIGHT: u32 = GLYPH_SIZE + PADDING;
+const HEADER_HEIGHT: u32 = CHAR_HEIGHT + PADDING * 2;
+
+const LABEL_BACKGROUND: image::Rgba<u8> = image::Rgba([32, 32, 32, 255]);
+const TEXT_COLOR: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+const GLYPH_TILE: image::Rgba<u8> = image::Rgba([128, 128, 128, 255]);
+const GLYPH_CONNECTED: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+const GLYPH_EMPTY: image::Rgba<u8> = image::Rgba([64, 64, 64, 255]);
+
+fn fill_rect(
+	img: &mut image::DynamicImage,
+	x: u32,
+	y: u32,
+	width: u32,
+	height: u32,
+	color: image::Rgba<u8>,
+) {
+	for pixel_y in y..y + height {
+		for pixel_x in x..x + width {
+			img.put_pixel(pixel_x, pixel_y, color);
+		}
+	}
+}
+
+///Writes the text with the 3x5 font, its top left corner at the given position. Characters missing from the font are left blank.
+fn draw_text(img: &mut image::DynamicImage, text: &str, x: u32, y: u32) {
+	for (index, character) in text.chars().enumerate() {
+		let rows = match FONT.iter().find(|(font_char, _)| *font_char == character) {
+			Some((_, rows)) => rows,
+			None => continue,
+		};
+		let char_x = x + index as u32 * CHAR_WIDTH;
+		for (row_y, row) in rows.iter().enumerate() {
+			for column in 0..3 {
+				if row & (0b100 >> column) != 0 {
+					img.put_pixel(char_x + column, y + row_y as u32, TEXT_COLOR);
+				};
+			}
+		}
+	}
+}
+
+///Which cell of the neighbor glyph each bit of the signature lights up, as (bit, column, row).
+fn glyph_cells(smoothing_mode: &config::SmoothingMode) -> Vec<(u8, u32, u32)> {
+	match smoothing_mode {
+		config::SmoothingMode::Hex { flat_top } => (0..glob::HEX_EDGES)
+			.map(|edge| {
+				let (x, y) = hex::edge_direction(edge, *flat_top);
+				(1 << edge, (x + 1) as u32, (y + 1) as u32)
+			})
+			.collect(),
+		_ => glob::ADJ_OFFSETS
+			.iter()
+			.map(|(adjacency, x, y)| (*adjacency, (x + 1) as u32, (y + 1) as u32))
+			.collect(),
+	}
+}
+
+fn draw_glyph(
+	img: &mut image::DynamicImage,
+	cells: &[(u8, u32, u32)],
+	signature: u8,
+	x: u32,
+	y: u32,
+) {
+	fill_rect(img, x, y, GLYPH_SIZE, GLYPH_SIZE, GLYPH_EMPTY);
+	fill_rect(
+		img,
+		x + GLYPH_CELL,
+		y + GLYPH_CELL,
+		GLYPH_CELL,
+		GLYPH_CELL,
+		GLYPH_TILE,
+	);
+	for (bit, column, row) in cells.iter() {
+		if signature & bit != glob::NONE {
+			fill_rect(
+				img,
+				x + column * GLYPH_CELL,
+				y + row * GLYPH_CELL,
+				GLYPH_CELL,
+				GLYPH_CELL,
+				GLYPH_CONNECTED,
+			);
+		};
+	}
+}
+
+///Saves every icon state's first frame in a grid, a block per dir, each labeled with its signature and neighbor glyph.
+pub fn save_contact_sheet(
+	prefs: &config::PrefHolder,
+	signatures: &[u8],
+	icon_states: &[icon::IconState],
+	icon_directions: &[u8],
+	output_name: &str,
+) -> Result<()> {
+	let cell_width = prefs
+		.output_icon_size_x
+		.max(GLYPH_SIZE + PADDING + CHAR_WIDTH * 3)
+		+ PADDING;
+	let cell_height = prefs.output_icon_size_y + LABEL_HEIGHT + PADDING;
+	let columns = (signatures.len() as f64).sqrt().ceil() as u32;
+	let rows = (signatures.len() as u32 + columns - 1) / columns;
+	let header_height = if icon_directions.len() > 1 {
+		HEADER_HEIGHT
+	} else {
+		0
+	};
+	let block_height = header_height + rows * cell_height;
+	let mut sheet = image::DynamicImage::new_rgba8(
+		columns * cell_width + PADDING,
+		icon_directions.len() as u32 * block_height + PADDING,
+	);
+	let cells = glyph_cells(&prefs.smoothing_mode);
+
+	for (dir_index, byond_dir) in icon_directions.iter().enumerate() {
+		let block_y = PADDING + dir_index as u32 * block_height;
+		if header_height > 0 {
+			let dir_name = config::byond_dir_name(*byond_dir).to_uppercase();
+			fill_rect(
+				&mut sheet,
+				PADDING,
+				block_y,
+				columns * cell_width - PADDING,
+				CHAR_HEIGHT + PADDING,
+				LABEL_BACKGROUND,
+			);
+			draw_text(&mut sheet, &dir_name, PADDING + 1, block_y + 1);
+		};
+		for (index, (signature, frames)) in
+			super::state_frames(signatures, icon_states, dir_index).enumerate()
+		{
+			let x = PADDING + (index as u32 % columns) * cell_width;
+			let y = block_y + header_height + (index as u32 / columns) * cell_height;
+			image::imageops::overlay(&mut sheet, &frames[0], x, y);
+			let label_y = y + prefs.output_icon_size_y + PADDING;
+			fill_rect(
+				&mut sheet,
+				x,
+				label_y,
+				cell_width - PADDING,
+				GLYPH_SIZE,
+				LABEL_BACKGROUND,
+			);
+			draw_glyph(&mut sheet, &cells, signature, x, label_y);
+			draw_text(
+				&mut sheet,
+				&signature.to_string(),
+				x + GLYPH_SIZE + PADDING,
+				label_y + (GLYPH_SIZE - CHAR_HEIGHT) / 2,
+			);
+		}
+	}
+
+	let sheet_name = format!("{}-states.png", output_name);
+	sheet.save(&sheet_name)?;
+	println!("Contact sheet saved as {}.", sheet_name);
+	Ok(())
+}
@@ -0,0 +1,375 @@
+use anyhow::Result;
+use image::GenericImageView;
+use std::collections::HashMap;
+
+use super::cache;
+use super::config;
+use super::glob;
+
+/*
+	* * DRY RUN
+	* Explains what a build would do, without writing anything: the settings as resolved, defaults worked out of other settings included,
+	* the rectangle each corner and prefab is cut from, which signatures the prefabs replace, and the files that would be written.
+	*/
+
+///The input sheet, and every other sheet a source is read from, by path.
+struct Sheets<'a> {
+	main: &'a config::SourceSheet,
+	others: &'a HashMap<String, config::SourceSheet>,
+}
+
+impl<'a> Sheets<'a> {
+	fn of(&self, source: &config::IconSource) -> &'a config::SourceSheet {
+		match &source.file {
+			Some(path) => &self.others[path],
+			None => self.main,
+		}
+	}
+}
+
+///Name of the corner, wedge or nine-slice piece, as used in the config.
+fn corner_name(smoothing_mode: &config::SmoothingMode, corner_dir: u8) -> String {
+	if let config::SmoothingMode::Hex { .. } = smoothing_mode {
+		return format!("wedge {}", corner_dir);
+	};
+	match corner_dir {
+		glob::NE_INDEX => "ne",
+		glob::SE_INDEX => "se",
+		glob::SW_INDEX => "sw",
+		glob::NW_INDEX => "nw",
+		glob::SLICE_NORTH => "north",
+		glob::SLICE_EAST => "east",
+		glob::SLICE_SOUTH => "south",
+		glob::SLICE_WEST => "west",
+		_ => "center",
+	}
+	.to_string()
+}
+
+fn corner_type_name(smoothing_mode: &config::SmoothingMode, corner_type: u8) -> &'static str {
+	if let config::SmoothingMode::Hex { .. } = smoothing_mode {
+		return match corner_type {
+			glob::HEX_CONVEX => "convex",
+			glob::HEX_COUNTERCLOCKWISE => "counterclockwise",
+			glob::HEX_CLOCKWISE => "clockwise",
+			_ => "flat",
+		};
+	};
+	match corner_type {
+		glob::CONVEX => "convex",
+		glob::CONCAVE => "concave",
+		glob::HORIZONTAL => "horizontal",
+		glob::VERTICAL => "vertical",
+		_ => "flat",
+	}
+}
+
+///Describes where the given frame of a source is cut from: its file, its cell in the sheet and the rectangle cropped.
+///Corners are cut with their corner dir, and whole icons without one.
+///Problems are written in place of the rectangle, so the rest of the table still gets printed.
+fn describe_source(
+	sheets: &Sheets,
+	var_name: &str,
+	source: &config::IconSource,
+	corner_dir: Option<u8>,
+	frame: u32,
+) -> Vec<String> {
+	let sheet = sheets.of(source);
+	let mut file = match &source.file {
+		Some(thing) => thing.clone(),
+		None => "input".to_string(),
+	};
+	if let Some(layer) = &source.layer {
+		file = format!("{} [{}]", file, layer);
+	};
+	let icon_rect = sheet.icon_rect(var_name, source, frame);
+	let cell = match (&source.position, &icon_rect) {
+		(config::IconPosition::Rect { .. }, _) | (_, Err(_)) => "-".to_string(),
+		(_, Ok((x, y, _, _))) => {
+			let layout = &sheet.layout;
+			format!(
+				"{}, {}",
+				(x - layout.sheet_margin) / (layout.icon_size_x + layout.sheet_spacing),
+				(y - layout.sheet_margin) / (layout.icon_size_y + layout.sheet_spacing)
+			)
+		}
+	};
+	let rect = match corner_dir {
+		Some(thing) => sheet.corner_rect(var_name, thing, source, frame),
+		None => icon_rect,
+	};
+	let rect = match rect {
+		Ok((x, y, width, height)) => {
+			let mut text = format!("{}x{} at ({}, {})", width, height, x, y);
+			if let Ok((img, _)) = sheet.sheet.frame_source(source.layer.as_deref(), frame) {
+				let (img_width, img_height) = img.dimensions();
+				if x + width > img_width || y + height > img_height {
+					text.push_str(&format!(
+						", outside of the {}x{} image",
+						img_width, img_height
+					));
+				};
+			};
+			text
+		}
+		Err(e) => format!("error: {}", e),
+	};
+	vec![var_name.to_string(), file, cell, frame.to_string(), rect]
+}
+
+///Prints the rows under the header, each column as wide as its widest entry.
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+	let mut widths: Vec<usize> = header.iter().map(|name| name.len()).collect();
+	for row in rows.iter() {
+		for (width, entry) in widths.iter_mut().zip(row.iter()) {
+			*width = (*width).max(entry.chars().count());
+		}
+	}
+	let format_row = |entries: Vec<String>| -> String {
+		let padded: Vec<String> = entries
+			.iter()
+			.zip(widths.iter())
+			.map(|(entry, width)| format!("{:width$}", entry, width = *width))
+			.collect();
+		padded.join("  ").trim_end().to_string()
+	};
+	println!(
+		"{}",
+		format_row(header.iter().map(|name| name.to_string()).collect())
+	);
+	for row in rows.iter() {
+		println!("{}", format_row(row.clone()));
+	}
+}
+
+///Prints which part of which sheet each corner of the given settings is cut from, overlays included.
+fn print_corners(prefs: &config::PrefHolder, sheets: &Sheets) -> Result<()> {
+	let mut rows = vec![];
+	for corner_dir in prefs.smoothing_mode.corner_dirs().iter() {
+		for corner_type in prefs.corner_types().iter() {
+			if !prefs
+				.smoothing_mode
+				.has_corner_type(*corner_dir, *corner_type)
+			{
+				continue;
+			};
+			let (var_name, source) = prefs.get_corner_source(*corner_dir, *corner_type)?;
+			let labels = vec![
+				corner_name(&prefs.smoothing_mode, *corner_dir),
+				corner_type_name(&prefs.smoothing_mode, *corner_type).to_string(),
+			];
+			for frame in 0..prefs.frames_per_state {
+				let mut row = labels.clone();
+				row.extend(describe_source(
+					sheets,
+					var_name,
+					source,
+					Some(*corner_dir),
+					frame,
+				));
+				rows.push(row);
+				for overlay in source.overlays.iter() {
+					let mut row = labels.clone();
+					row.extend(describe_source(
+						sheets,
+						&format!("{} overlay", var_name),
+						&overlay.source,
+						Some(*corner_dir),
+						frame,
+					));
+					rows.push(row);
+				}
+			}
+		}
+	}
+	print_table(
+		&["corner", "type", "config", "file", "cell", "frame", "rect"],
+		&rows,
+	);
+	Ok(())
+}
+
+///Prints which part of which sheet each prefab is cut from, and whether the signature it replaces is ever produced.
+fn print_prefabs(prefs: &config::PrefHolder, sheets: &Sheets, signatures: &[u8]) {
+	let prefabs = match &prefs.prefabs {
+		Some(thing) if !thing.is_empty() => thing,
+		_ => {
+			println!("No prefabs, every icon state is assembled out of corners.");
+			return;
+		}
+	};
+	let mut rows = vec![];
+	for (signature, source) in prefabs.iter() {
+		let replaces = if signatures.contains(signature) {
+			format!("{}", signature)
+		} else {
+			format!("{} (never produced)", signature)
+		};
+		// Overlays of the prefab's own source are drawn first, then those of prefab_overlays.
+		let source_overlay_name = format!("prefab {} overlay", signature);
+		let mut overlays: Vec<(&str, &config::IconOverlay)> = source
+			.overlays
+			.iter()
+			.map(|overlay| (source_overlay_name.as_str(), overlay))
+			.collect();
+		if let Some(thing) = prefs
+			.prefab_overlays
+			.as_ref()
+			.and_then(|prefab_overlays| prefab_overlays.get(signature))
+		{
+			overlays.extend(thing.iter().map(|overlay| ("prefab_overlays", overlay)));
+		};
+		for frame in 0..prefs.frames_per_state {
+			let mut row = vec![replaces.clone()];
+			row.extend(describe_source(
+				sheets,
+				&format!("prefab {}", signature),
+				source,
+				None,
+				frame,
+			));
+			rows.push(row);
+			for (var_name, overlay) in overlays.iter() {
+				let mut row = vec![replaces.clone()];
+				row.extend(describe_source(
+					sheets,
+					var_name,
+					&overlay.source,
+					None,
+					frame,
+				));
+				rows.push(row);
+			}
+		}
+	}
+	print_table(
+		&["replaces", "config", "file", "cell", "frame", "rect"],
+		&rows,
+	);
+	if let Some(thing) = &prefs.prefab_overlays {
+		for signature in thing.keys() {
+			if !prefabs.contains_key(signature) {
+				println!(
+					"Prefab overlays are defined for signature {}, which has no prefab. The build would fail.",
+					signature
+				);
+			};
+		}
+	};
+}
+
+fn print_settings(prefs: &config::PrefHolder, main_sheet: &config::SourceSheet, input_name: &str) {
+	let (width, height) = main_sheet.sheet.frames[0].dimensions();
+	println!(
+		"input: {}, {}x{} pixels, {} icons wide, room for {} in total",
+		input_name, width, height, main_sheet.width_in_frames, main_sheet.max_total_frames
+	);
+	if main_sheet.sheet.is_animated() {
+		println!(
+			"animated input, with {} frames",
+			main_sheet.sheet.frames.len()
+		);
+	};
+	println!(
+		"icon size: {}x{}, sheet margin: {}, sheet spacing: {}",
+		prefs.icon_size_x, prefs.icon_size_y, prefs.sheet_margin, prefs.sheet_spacing
+	);
+	println!("smoothing mode: {:?}", prefs.smoothing_mode);
+	if prefs.smoothing_mode == config::SmoothingMode::Quadrants {
+		for (name, start, step) in [
+			("west", prefs.west_start, prefs.west_step),
+			("east", prefs.east_start, prefs.east_step),
+			("north", prefs.north_start, prefs.north_step),
+			("south", prefs.south_start, prefs.south_step),
+		]
+		.iter()
+		{
+			println!(
+				"{}: from {} to {} ({} pixels)",
+				name,
+				start,
+				start + step,
+				step
+			);
+		}
+	};
+	println!(
+		"output icon size: {}x{}, output west start: {}, output east start: {}, output north start: {}, output south start: {}",
+		prefs.output_icon_size_x,
+		prefs.output_icon_size_y,
+		prefs.output_west_start,
+		prefs.output_east_start,
+		prefs.output_north_start,
+		prefs.output_south_start
+	);
+	let draw_order: Vec<String> = prefs
+		.corner_draw_order
+		.iter()
+		.map(|corner_dir| corner_name(&prefs.smoothing_mode, *corner_dir))
+		.collect();
+	println!(
+		"corner draw order: {}, seam feather: {}",
+		draw_order.join(", "),
+		prefs.seam_feather
+	);
+	println!(
+		"frames per state: {}, delays: {:?}",
+		prefs.frames_per_state, prefs.delay
+	);
+	println!("diagonal: {}", prefs.is_diagonal);
+}
+
+///Prints how the input would be cut and what would be written, without writing anything.
+pub fn explain(
+	prefs: &config::PrefHolder,
+	input: Vec<u8>,
+	input_name: &str,
+	output_name: &str,
+	signatures: &[u8],
+	icon_directions: &[u8],
+) -> Result<()> {
+	let (main_sheet, other_sheets) = prefs.load_source_sheets(input, input_name)?;
+	let sheets = Sheets {
+		main: &main_sheet,
+		others: &other_sheets,
+	};
+
+	println!("== Settings ==");
+	print_settings(prefs, &main_sheet, input_name);
+	let dir_names: Vec<String> = icon_directions
+		.iter()
+		.map(|byond_dir| match prefs.dir_strategies.get(byond_dir) {
+			Some(strategy) => format!("{} ({:?})", config::byond_dir_name(*byond_dir), strategy),
+			None => config::byond_dir_name(*byond_dir).to_string(),
+		})
+		.collect();
+	println!("dirs produced: {}", dir_names.join(", "));
+	println!("{} icon states per dir", signatures.len());
+
+	println!("== Corners ==");
+	print_corners(prefs, &sheets)?;
+	for byond_dir in prefs.dir_sources.keys() {
+		println!("== Corners for {} ==", config::byond_dir_name(*byond_dir));
+		print_corners(&prefs.for_dir(*byond_dir)?, &sheets)?;
+	}
+
+	println!("== Prefabs ==");
+	print_prefabs(prefs, &sheets, signatures);
+
+	println!("== Files that would be written ==");
+	let seams_name = format!("{}-seams.png", output_name);
+	for file_name in super::target_files(prefs, output_name).iter() {
+		if *file_name == seams_name {
+			println!("{}, if any seams don't line up", file_name);
+		} else {
+			println!("{}", file_name);
+		};
+	}
+	if prefs.incremental {
+		println!(
+			"{}, updated with the hashes of the files written",
+			super::output_dir(prefs).join(cache::CACHE_FILE).display()
+		);
+	};
+	Ok(())
+}
@@ -15,6 +15,25 @@ pub const NW_INDEX: u8 = 3;
 
 pub const CORNER_DIRS: [u8; 4] = [NE_INDEX, SE_INDEX, SW_INDEX, NW_INDEX];
 
+//Nine-slice pieces, besides the four corners.
+pub const SLICE_NORTH: u8 = 4;
+pub const SLICE_EAST: u8 = 5;
+pub const SLICE_SOUTH: u8 = 6;
+pub const SLICE_WEST: u8 = 7;
+pub const SLICE_CENTER: u8 = 8;
+
+pub const NINE_SLICE_PIECES: [u8; 9] = [
+	NE_INDEX,
+	SE_INDEX,
+	SW_INDEX,
+	NW_INDEX,
+	SLICE_NORTH,
+	SLICE_EAST,
+	SLICE_SOUTH,
+	SLICE_WEST,
+	SLICE_CENTER,
+];
+
 pub const CONVEX: u8 = 0;
 pub const CONCAVE: u8 = 1;
 pub const HORIZONTAL: u8 = 2;
@@ -24,6 +43,18 @@ pub const FLAT: u8 = 4;
 pub const CORNER_TYPES_CARDINAL: [u8; 4] = [CONVEX, CONCAVE, HORIZONTAL, VERTICAL];
 pub const CORNER_TYPES_DIAGONAL: [u8; 5] = [CONVEX, CONCAVE, HORIZONTAL, VERTICAL, FLAT];
 
+//Hex tiles. Each bit of a hex signature is one of the six edges, clockwise.
+pub const HEX_EDGES: u8 = 6;
+pub const HEX_WEDGES: [u8; 6] = [0, 1, 2, 3, 4, 5];
+
+//Hex wedge types, by which of the two edges around the wedge connect.
+pub const HEX_CONVEX: u8 = 0;
+pub const HEX_COUNTERCLOCKWISE: u8 = 1;
+pub const HEX_CLOCKWISE: u8 = 2;
+pub const HEX_FLAT: u8 = 3;
+
+pub const HEX_WEDGE_TYPES: [u8; 4] = [HEX_CONVEX, HEX_COUNTERCLOCKWISE, HEX_CLOCKWISE, HEX_FLAT];
+
 //Dirs
 pub const NONE: u8 = 0;
 
@@ -38,6 +69,19 @@ pub const ADJ_SW: u8 = 1 << 6;
 pub const ADJ_NW: u8 = 1 << 7;
 
 pub const ADJ_CARDINALS: [u8; 4] = [ADJ_N, ADJ_E, ADJ_S, ADJ_W];
+//Every adjacency dir, clockwise from the north one.
+pub const ADJ_CLOCKWISE: [u8; 8] = [ADJ_N, ADJ_NE, ADJ_E, ADJ_SE, ADJ_S, ADJ_SW, ADJ_W, ADJ_NW];
+//Map offsets (x, y) of each adjacency dir, y growing southwards.
+pub const ADJ_OFFSETS: [(u8, i32, i32); 8] = [
+	(ADJ_N, 0, -1),
+	(ADJ_NE, 1, -1),
+	(ADJ_E, 1, 0),
+	(ADJ_SE, 1, 1),
+	(ADJ_S, 0, 1),
+	(ADJ_SW, -1, 1),
+	(ADJ_W, -1, 0),
+	(ADJ_NW, -1, -1),
+];
 
 pub const ADJ_N_S: u8 = ADJ_N | ADJ_S;
 pub const ADJ_E_W: u8 = ADJ_E | ADJ_W;
@@ -48,5 +92,19 @@ pub const BYOND_NORTH: u8 = 1 << 0;
 pub const BYOND_SOUTH: u8 = 1 << 1;
 pub const BYOND_EAST: u8 = 1 << 2;
 pub const BYOND_WEST: u8 = 1 << 3;
+pub const BYOND_NORTHEAST: u8 = BYOND_NORTH | BYOND_EAST;
+pub const BYOND_SOUTHEAST: u8 = BYOND_SOUTH | BYOND_EAST;
+pub const BYOND_SOUTHWEST: u8 = BYOND_SOUTH | BYOND_WEST;
+pub const BYOND_NORTHWEST: u8 = BYOND_NORTH | BYOND_WEST;
 //Order is relevant here. This is the order in which the dmi directions are set.
 pub const BYOND_CARDINALS: [u8; 4] = [BYOND_SOUTH, BYOND_NORTH, BYOND_EAST, BYOND_WEST];
+pub const BYOND_ALL_DIRS: [u8; 8] = [
+	BYOND_SOUTH,
+	BYOND_NORTH,
+	BYOND_EAST,
+	BYOND_WEST,
+	BYOND_SOUTHEAST,
+	BYOND_SOUTHWEST,
+	BYOND_NORTHEAST,
+	BYOND_NORTHWEST,
+];
@@ -134,6 +134,22 @@ pub fn dir_offset_signature(icon_signature: u8, byond_dir: u8) -> Result<u8, dmi
 			all_junctions[6] = all_junctions[6] >> 1;
 			all_junctions[7] = all_junctions[7] >> 1;
 		}
+		glob::BYOND_SOUTHEAST => {
+			//Counter-clockwise 45 degrees rotation.
+			return Ok(rotate_signature(icon_signature, 7));
+		}
+		glob::BYOND_SOUTHWEST => {
+			//Clockwise 45 degrees rotation.
+			return Ok(rotate_signature(icon_signature, 1));
+		}
+		glob::BYOND_NORTHEAST => {
+			//Counter-clockwise 135 degrees rotation.
+			return Ok(rotate_signature(icon_signature, 5));
+		}
+		glob::BYOND_NORTHWEST => {
+			//Clockwise 135 degrees rotation.
+			return Ok(rotate_signature(icon_signature, 3));
+		}
 		glob::BYOND_WEST => {
 			//Clockwise 90 degrees rotation.
 			all_junctions[0] = all_junctions[0] << 2;
@@ -166,6 +182,56 @@ pub fn dir_offset_signature(icon_signature: u8, byond_dir: u8) -> Result<u8, dmi
 	//let mut offset_signature = ((icon_signature & 0b0101) << 1) | ((icon_signature & 0b1010) >> 1);
 }
 
+///Rotates the junctions clockwise, in steps of 45 degrees.
+///Diagonal rotations may leave diagonal junctions without their adjacent cardinals, so the result should go through smooth_dir_to_combination_key().
+pub fn rotate_signature(icon_signature: u8, steps: usize) -> u8 {
+	let mut rotated_signature = glob::NONE;
+	for (index, dir) in glob::ADJ_CLOCKWISE.iter().enumerate() {
+		if icon_signature & dir != glob::NONE {
+			rotated_signature |= glob::ADJ_CLOCKWISE[(index + steps) % glob::ADJ_CLOCKWISE.len()];
+		}
+	}
+	rotated_signature
+}
+
+///Mirrors the junctions across the diagonal axis the given diagonal dir lies on.
+pub fn mirror_signature_diagonally(
+	icon_signature: u8,
+	byond_dir: u8,
+) -> Result<u8, dmi::error::DmiError> {
+	match byond_dir {
+		glob::BYOND_NORTHEAST | glob::BYOND_SOUTHWEST => Ok(mirror_signature(icon_signature, 1)),
+		glob::BYOND_SOUTHEAST | glob::BYOND_NORTHWEST => Ok(mirror_signature(icon_signature, 3)),
+		_ => Err(error::DmiError::Generic(format!(
+			"mirror_signature_diagonally called with invalid byond_dir: {}",
+			byond_dir
+		))),
+	}
+}
+
+///Mirrors the junctions across the axis going through the given position of glob::ADJ_CLOCKWISE and its opposite.
+///0 mirrors west to east, 2 north to south.
+pub fn mirror_signature(icon_signature: u8, axis: usize) -> u8 {
+	let mut mirrored_signature = glob::NONE;
+	for (index, dir) in glob::ADJ_CLOCKWISE.iter().enumerate() {
+		if icon_signature & dir != glob::NONE {
+			mirrored_signature |= glob::ADJ_CLOCKWISE[(2 * axis + 8 - index) % 8];
+		}
+	}
+	mirrored_signature
+}
+
+///Moves each junction to another one. The permutation holds, for each position in glob::ADJ_CLOCKWISE, the position it is moved to.
+pub fn permute_signature(icon_signature: u8, permutation: &[usize; 8]) -> u8 {
+	let mut permuted_signature = glob::NONE;
+	for (index, dir) in glob::ADJ_CLOCKWISE.iter().enumerate() {
+		if icon_signature & dir != glob::NONE {
+			permuted_signature |= glob::ADJ_CLOCKWISE[permutation[index]];
+		}
+	}
+	permuted_signature
+}
+
 ///Takes everything that comes before the first dot in the string, discarding the rest.
 pub fn trim_path_after_first_dot(mut text: String) -> String {
 	let dot_offset = text.find('.').unwrap_or(text.len());
@@ -198,3 +264,185 @@ pub fn hash_set_lazy_add(hash: Option<HashSet<u8>>, value: u8) -> Option<HashSet
 	unwrapped.insert(value);
 	Some(unwrapped)
 }
+
+#[cfg(test)]
+mod tests {
+	// Signatures are a single byte, so every property is checked against all 256 of them rather than a random sample.
+	use super::*;
+
+	fn all_signatures() -> impl Iterator<Item = u8> {
+		glob::NONE..=glob::ADJ_ALL
+	}
+
+	///The two cardinals each diagonal lies between.
+	const DIAGONAL_CARDINALS: [(u8, u8, u8); 4] = [
+		(glob::ADJ_NE, glob::ADJ_N, glob::ADJ_E),
+		(glob::ADJ_SE, glob::ADJ_S, glob::ADJ_E),
+		(glob::ADJ_SW, glob::ADJ_S, glob::ADJ_W),
+		(glob::ADJ_NW, glob::ADJ_N, glob::ADJ_W),
+	];
+
+	///The quarter turns dir_offset_signature applies, by dir, as clockwise steps of 45 degrees.
+	const QUARTER_TURNS: [(u8, usize); 4] = [
+		(glob::BYOND_SOUTH, 0),
+		(glob::BYOND_WEST, 2),
+		(glob::BYOND_NORTH, 4),
+		(glob::BYOND_EAST, 6),
+	];
+
+	#[test]
+	fn dir_offsets_match_rotations() {
+		for (byond_dir, steps) in QUARTER_TURNS.iter() {
+			for signature in all_signatures() {
+				assert_eq!(
+					dir_offset_signature(signature, *byond_dir).unwrap(),
+					rotate_signature(signature, *steps),
+					"signature {}, dir {}",
+					signature,
+					byond_dir
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn four_rotations_are_the_identity() {
+		for signature in all_signatures() {
+			for (byond_dir, _) in QUARTER_TURNS.iter() {
+				let mut rotated = signature;
+				for _ in 0..4 {
+					rotated = dir_offset_signature(rotated, *byond_dir).unwrap();
+				}
+				assert_eq!(rotated, signature, "dir {}", byond_dir);
+			}
+			for steps in 1..8 {
+				let mut rotated = signature;
+				for _ in 0..8 {
+					rotated = rotate_signature(rotated, steps);
+				}
+				assert_eq!(rotated, signature, "{} steps", steps);
+			}
+		}
+	}
+
+	#[test]
+	fn opposite_rotations_cancel_out() {
+		for signature in all_signatures() {
+			let east = dir_offset_signature(signature, glob::BYOND_EAST).unwrap();
+			assert_eq!(
+				dir_offset_signature(east, glob::BYOND_WEST).unwrap(),
+				signature
+			);
+			let north = dir_offset_signature(signature, glob::BYOND_NORTH).unwrap();
+			assert_eq!(
+				dir_offset_signature(north, glob::BYOND_NORTH).unwrap(),
+				signature
+			);
+		}
+	}
+
+	#[test]
+	fn rotations_commute_with_combination_keys() {
+		for is_diagonal in [false, true].iter() {
+			for (byond_dir, _) in QUARTER_TURNS.iter() {
+				for signature in all_signatures() {
+					let reduced_then_rotated = dir_offset_signature(
+						smooth_dir_to_combination_key(signature, *is_diagonal),
+						*byond_dir,
+					)
+					.unwrap();
+					let rotated_then_reduced = smooth_dir_to_combination_key(
+						dir_offset_signature(signature, *byond_dir).unwrap(),
+						*is_diagonal,
+					);
+					assert_eq!(
+						reduced_then_rotated, rotated_then_reduced,
+						"signature {}, dir {}, diagonal {}",
+						signature, byond_dir, is_diagonal
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn diagonals_need_both_adjacent_cardinals() {
+		for signature in all_signatures() {
+			let key = smooth_dir_to_combination_key(signature, true);
+			for (diagonal, first_cardinal, second_cardinal) in DIAGONAL_CARDINALS.iter() {
+				let kept = signature & diagonal != glob::NONE
+					&& signature & first_cardinal != glob::NONE
+					&& signature & second_cardinal != glob::NONE;
+				assert_eq!(
+					key & diagonal != glob::NONE,
+					kept,
+					"signature {}, diagonal {}",
+					signature,
+					diagonal
+				);
+			}
+			let cardinals = glob::ADJ_N | glob::ADJ_S | glob::ADJ_E | glob::ADJ_W;
+			assert_eq!(
+				key & cardinals,
+				signature & cardinals,
+				"signature {}",
+				signature
+			);
+			assert_eq!(
+				smooth_dir_to_combination_key(signature, false),
+				signature & cardinals,
+				"signature {}",
+				signature
+			);
+		}
+	}
+
+	#[test]
+	fn combination_keys_are_already_reduced() {
+		for is_diagonal in [false, true].iter() {
+			for signature in all_signatures() {
+				let key = smooth_dir_to_combination_key(signature, *is_diagonal);
+				assert_eq!(smooth_dir_to_combination_key(key, *is_diagonal), key);
+			}
+		}
+	}
+
+	#[test]
+	fn corner_types_only_depend_on_combination_keys() {
+		for signature in all_signatures() {
+			let key = smooth_dir_to_combination_key(signature, true);
+			for corner in glob::CORNER_DIRS.iter() {
+				assert_eq!(
+					smooth_dir_to_corner_type(*corner, signature),
+					smooth_dir_to_corner_type(*corner, key),
+					"signature {}, corner {}",
+					signature,
+					corner
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn corner_types_rotate_with_their_signature() {
+		// A quarter turn clockwise moves each corner to the next one, and turns horizontal edges into vertical ones and back.
+		let turned_type = |corner_type: u8| match corner_type {
+			glob::HORIZONTAL => glob::VERTICAL,
+			glob::VERTICAL => glob::HORIZONTAL,
+			_ => corner_type,
+		};
+		for signature in all_signatures() {
+			let rotated = rotate_signature(signature, 2);
+			for (index, corner) in glob::CORNER_DIRS.iter().enumerate() {
+				let turned_corner = glob::CORNER_DIRS[(index + 1) % glob::CORNER_DIRS.len()];
+				assert_eq!(
+					smooth_dir_to_corner_type(turned_corner, rotated),
+					turned_type(smooth_dir_to_corner_type(*corner, signature)),
+					"signature {}, corner {}",
+					signature,
+					corner
+				);
+			}
+		}
+	}
+}
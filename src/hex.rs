@@ -0,0 +1,103 @@
+use image::GenericImage;
+use image::GenericImageView;
+
+use super::glob;
+
+/*
+	* * HEXAGONAL TILES
+	* The tile is a hexagon filling the icon. Its six edges are numbered clockwise, starting from the northeast one for pointy-top tiles
+	* and from the north one for flat-top tiles, and bit N of the signature is set if the neighbor across edge N connects.
+	* The tile is cut into six wedges, one per tip, each spanning from the middle of the edge before it to the middle of the edge after it.
+	*/
+
+///Offsets from the center of the tile to the middle of each edge, as fractions of the icon's width and height.
+const POINTY_EDGE_MIDDLES: [(f32, f32); 6] = [
+	(0.25, -0.375),
+	(0.5, 0.0),
+	(0.25, 0.375),
+	(-0.25, 0.375),
+	(-0.5, 0.0),
+	(-0.25, -0.375),
+];
+const FLAT_EDGE_MIDDLES: [(f32, f32); 6] = [
+	(0.0, -0.5),
+	(0.375, -0.25),
+	(0.375, 0.25),
+	(0.0, 0.5),
+	(-0.375, 0.25),
+	(-0.375, -0.25),
+];
+
+///Which way the given edge faces from the center of the tile, as -1, 0 or 1 along each axis.
+pub fn edge_direction(edge: u8, flat_top: bool) -> (i32, i32) {
+	let edge_middles = if flat_top {
+		&FLAT_EDGE_MIDDLES
+	} else {
+		&POINTY_EDGE_MIDDLES
+	};
+	let (x, y) = edge_middles[edge as usize];
+	let sign = |offset: f32| -> i32 {
+		if offset == 0.0 {
+			0
+		} else {
+			offset.signum() as i32
+		}
+	};
+	(sign(x), sign(y))
+}
+
+///Returns which wedge of a hexagonal tile the given pixel belongs to.
+///Wedge N lies between edge N and the one clockwise from it. Pixels outside of the hexagon belong to the wedge in their direction.
+pub fn wedge_of_pixel(x: u32, y: u32, width: u32, height: u32, flat_top: bool) -> u8 {
+	let edge_middles = if flat_top {
+		&FLAT_EDGE_MIDDLES
+	} else {
+		&POINTY_EDGE_MIDDLES
+	};
+	let px = x as f32 + 0.5 - width as f32 / 2.0;
+	let py = y as f32 + 0.5 - height as f32 / 2.0;
+	// Clockwise on screen, as y grows downwards.
+	let cross = |(ax, ay): (f32, f32), (bx, by): (f32, f32)| ax * by - ay * bx;
+	for wedge in glob::HEX_WEDGES.iter() {
+		let (start_x, start_y) = edge_middles[*wedge as usize];
+		let (end_x, end_y) = edge_middles[(*wedge as usize + 1) % glob::HEX_WEDGES.len()];
+		let start = (start_x * width as f32, start_y * height as f32);
+		let end = (end_x * width as f32, end_y * height as f32);
+		if cross(start, (px, py)) >= 0.0 && cross((px, py), end) > 0.0 {
+			return *wedge;
+		};
+	}
+	// Only the very center of the tile can get here.
+	glob::HEX_WEDGES[0]
+}
+
+///Clears every pixel of a whole hexagonal tile that doesn't belong to the given wedge.
+pub fn mask_wedge(img: &image::DynamicImage, wedge: u8, flat_top: bool) -> image::DynamicImage {
+	let mut wedge_img = img.clone();
+	let (width, height) = img.dimensions();
+	for y in 0..height {
+		for x in 0..width {
+			if wedge_of_pixel(x, y, width, height, flat_top) != wedge {
+				wedge_img.put_pixel(x, y, image::Rgba([0; 4]));
+			};
+		}
+	}
+	wedge_img
+}
+
+///The hex counterpart of helpers::smooth_dir_to_corner_type, which wedge type to use given the neighbors in the signature.
+pub fn wedge_type(wedge: u8, signature: u8) -> u8 {
+	let counterclockwise_edge = signature & (1 << wedge) != glob::NONE;
+	let clockwise_edge = signature & (1 << ((wedge + 1) % glob::HEX_EDGES)) != glob::NONE;
+	match (counterclockwise_edge, clockwise_edge) {
+		(false, false) => glob::HEX_CONVEX,
+		(true, false) => glob::HEX_COUNTERCLOCKWISE,
+		(false, true) => glob::HEX_CLOCKWISE,
+		(true, true) => glob::HEX_FLAT,
+	}
+}
+
+///Every hex signature, one per combination of the six neighbors.
+pub fn all_signatures() -> Vec<u8> {
+	(0..1 << glob::HEX_EDGES).collect()
+}
@@ -0,0 +1,394 @@
+use anyhow::bail;
+use anyhow::Result;
+use std::collections::HashMap;
+
+///A paletted re-encoding of a PNG file.
+pub struct IndexedPng {
+	pub bytes: Vec<u8>,
+	///Number of colors in the palette.
+	pub colors: usize,
+	///Whether colors had to be merged to fit in the palette.
+	pub quantized: bool,
+}
+
+///Re-encodes an RGBA PNG, such as a DMI file, as a paletted one, keeping its text chunks (where the DMI metadata lives).
+///If the image holds more colors than max_colors allows (or 256, if unset) they are reduced to fit, but only if max_colors is set.
+///Otherwise None is returned, as the image can't be converted losslessly.
+pub fn to_indexed_png(png_bytes: &[u8], max_colors: Option<u32>) -> Result<Option<IndexedPng>> {
+	let img = image::load_from_memory_with_format(png_bytes, image::ImageFormat::Png)?.into_rgba8();
+	let (width, height) = img.dimensions();
+
+	// Fully transparent pixels all look the same, whatever their color channels say.
+	let pixels: Vec<[u8; 4]> = img
+		.pixels()
+		.map(|pixel| if pixel[3] == 0 { [0; 4] } else { pixel.0 })
+		.collect();
+	let mut color_count: HashMap<[u8; 4], u32> = HashMap::new();
+	for pixel in pixels.iter() {
+		*color_count.entry(*pixel).or_insert(0) += 1;
+	}
+
+	let palette_limit = max_colors.unwrap_or(256) as usize;
+	let quantized = color_count.len() > palette_limit;
+	let mut palette: Vec<[u8; 4]>;
+	if !quantized {
+		palette = color_count.keys().copied().collect();
+	} else if max_colors.is_some() {
+		palette = median_cut(&color_count, palette_limit);
+	} else {
+		return Ok(None);
+	};
+	// Transparent colors first, so the tRNS chunk can be cut short.
+	palette.sort_by_key(|color| (color[3], *color));
+
+	let mut color_index: HashMap<[u8; 4], u8> = HashMap::new();
+	for color in color_count.keys() {
+		let index = match palette.iter().position(|entry| entry == color) {
+			Some(thing) => thing,
+			None => nearest_color(&palette, color),
+		};
+		color_index.insert(*color, index as u8);
+	}
+
+	let bit_depth = match palette.len() {
+		0..=2 => png::BitDepth::One,
+		3..=4 => png::BitDepth::Two,
+		5..=16 => png::BitDepth::Four,
+		_ => png::BitDepth::Eight,
+	};
+	let bits = bit_depth as usize;
+	let row_length = (width as usize * bits + 7) / 8;
+	let mut image_data = vec![0u8; row_length * height as usize];
+	for (pixel_index, pixel) in pixels.iter().enumerate() {
+		let x = pixel_index % width as usize;
+		let y = pixel_index / width as usize;
+		let bit_offset = x * bits;
+		let shift = 8 - bits - bit_offset % 8;
+		image_data[y * row_length + bit_offset / 8] |= color_index[pixel] << shift;
+	}
+
+	let mut plte = vec![];
+	let mut trns = vec![];
+	for color in palette.iter() {
+		plte.extend_from_slice(&color[..3]);
+		trns.push(color[3]);
+	}
+	while trns.last() == Some(&255) {
+		trns.pop();
+	}
+
+	let mut bytes = vec![];
+	{
+		let mut encoder = png::Encoder::new(&mut bytes, width, height);
+		encoder.set_color(png::ColorType::Indexed);
+		encoder.set_depth(bit_depth);
+		encoder.set_palette(plte);
+		if !trns.is_empty() {
+			encoder.set_trns(trns);
+		};
+		encoder.set_compression(png::Compression::Best);
+		// Filtering rarely helps paletted images, as neighboring indexes aren't related the way colors are.
+		encoder.set_filter(png::FilterType::NoFilter);
+		let mut writer = encoder.write_header()?;
+		for (name, data) in text_chunks(png_bytes)? {
+			writer.write_chunk(name, data)?;
+		}
+		writer.write_image_data(&image_data)?;
+	}
+	Ok(Some(IndexedPng {
+		bytes,
+		colors: palette.len(),
+		quantized,
+	}))
+}
+
+///Re-encodes an RGBA PNG, such as a DMI file, with fixed settings, so the same pixels and metadata always give the same bytes
+///whatever wrote them first. Only the text chunks (where the DMI metadata lives) are kept, in their order, so no timestamps sneak in.
+pub fn to_canonical_png(png_bytes: &[u8]) -> Result<Vec<u8>> {
+	let mut img =
+		image::load_from_memory_with_format(png_bytes, image::ImageFormat::Png)?.into_rgba8();
+	let (width, height) = img.dimensions();
+	// Fully transparent pixels all look the same, whatever their color channels say.
+	for pixel in img.pixels_mut() {
+		if pixel[3] == 0 {
+			*pixel = image::Rgba([0; 4]);
+		};
+	}
+
+	let mut bytes = vec![];
+	{
+		let mut encoder = png::Encoder::new(&mut bytes, width, height);
+		encoder.set_color(png::ColorType::RGBA);
+		encoder.set_depth(png::BitDepth::Eight);
+		encoder.set_compression(png::Compression::Best);
+		encoder.set_filter(png::FilterType::Paeth);
+		let mut writer = encoder.write_header()?;
+		for (name, data) in text_chunks(png_bytes)? {
+			writer.write_chunk(name, data)?;
+		}
+		writer.write_image_data(&img)?;
+	}
+	Ok(bytes)
+}
+
+///Returns the tEXt, zTXt and iTXt chunks of a PNG file, in order.
+fn text_chunks(png_bytes: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+	let mut chunks = vec![];
+	let mut offset = 8; // Skip the signature.
+	while offset + 8 <= png_bytes.len() {
+		let length = u32::from_be_bytes([
+			png_bytes[offset],
+			png_bytes[offset + 1],
+			png_bytes[offset + 2],
+			png_bytes[offset + 3],
+		]) as usize;
+		let name = [
+			png_bytes[offset + 4],
+			png_bytes[offset + 5],
+			png_bytes[offset + 6],
+			png_bytes[offset + 7],
+		];
+		let data_start = offset + 8;
+		if data_start + length + 4 > png_bytes.len() {
+			bail!("Truncated PNG chunk {:?}", String::from_utf8_lossy(&name));
+		};
+		if &name == b"tEXt" || &name == b"zTXt" || &name == b"iTXt" {
+			chunks.push((name, &png_bytes[data_start..data_start + length]));
+		};
+		offset = data_start + length + 4; // Chunk data plus its CRC.
+	}
+	Ok(chunks)
+}
+
+///Reduces the colors to the given amount by splitting them into boxes along their widest channel, then averaging each box.
+///Fully transparent pixels keep a palette entry of their own.
+fn median_cut(color_count: &HashMap<[u8; 4], u32>, max_colors: usize) -> Vec<[u8; 4]> {
+	let mut palette = vec![];
+	let mut colors: Vec<([u8; 4], u32)> = color_count
+		.iter()
+		.filter(|(color, _)| color[3] != 0)
+		.map(|(color, count)| (*color, *count))
+		.collect();
+	colors.sort();
+	let mut boxes_allowed = max_colors;
+	if colors.len() < color_count.len() {
+		palette.push([0; 4]);
+		boxes_allowed -= 1;
+	};
+	let mut boxes = vec![colors];
+	while boxes.len() < boxes_allowed {
+		// The box whose colors are the most spread out along any channel is split next.
+		let mut widest = None;
+		for (box_index, color_box) in boxes.iter().enumerate() {
+			if color_box.len() < 2 {
+				continue;
+			};
+			for channel in 0..4 {
+				let min = color_box
+					.iter()
+					.map(|entry| entry.0[channel])
+					.min()
+					.unwrap();
+				let max = color_box
+					.iter()
+					.map(|entry| entry.0[channel])
+					.max()
+					.unwrap();
+				let range = max - min;
+				if widest.map_or(true, |(_, _, widest_range)| range > widest_range) {
+					widest = Some((box_index, channel, range));
+				};
+			}
+		}
+		let (box_index, channel) = match widest {
+			Some((box_index, channel, _)) => (box_index, channel),
+			None => break,
+		};
+		let mut color_box = boxes.swap_remove(box_index);
+		color_box.sort_by_key(|entry| (entry.0[channel], entry.0));
+		let total: u32 = color_box.iter().map(|entry| entry.1).sum();
+		let mut running = 0;
+		let mut split = 1;
+		for (index, entry) in color_box.iter().enumerate() {
+			running += entry.1;
+			if running * 2 >= total {
+				split = (index + 1).max(1).min(color_box.len() - 1);
+				break;
+			};
+		}
+		let upper = color_box.split_off(split);
+		boxes.push(color_box);
+		boxes.push(upper);
+	}
+	for color_box in boxes.iter() {
+		if color_box.is_empty() {
+			continue;
+		};
+		let total: u64 = color_box.iter().map(|entry| entry.1 as u64).sum();
+		let mut average = [0u8; 4];
+		for channel in 0..4 {
+			let sum: u64 = color_box
+				.iter()
+				.map(|entry| entry.0[channel] as u64 * entry.1 as u64)
+				.sum();
+			average[channel] = ((sum + total / 2) / total) as u8;
+		}
+		palette.push(average);
+	}
+	palette
+}
+
+fn nearest_color(palette: &[[u8; 4]], color: &[u8; 4]) -> usize {
+	let distance = |entry: &[u8; 4]| -> u32 {
+		(0..4)
+			.map(|channel| {
+				let difference = entry[channel] as i32 - color[channel] as i32;
+				(difference * difference) as u32
+			})
+			.sum()
+	};
+	let mut nearest = 0;
+	for (index, entry) in palette.iter().enumerate() {
+		if distance(entry) < distance(&palette[nearest]) {
+			nearest = index;
+		};
+	}
+	nearest
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	///Encodes the pixels as an RGBA PNG with a tEXt chunk, as a DMI file would be.
+	fn rgba_png(width: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+		let height = pixels.len() as u32 / width;
+		let mut bytes = vec![];
+		{
+			let mut encoder = png::Encoder::new(&mut bytes, width, height);
+			encoder.set_color(png::ColorType::RGBA);
+			encoder.set_depth(png::BitDepth::Eight);
+			let mut writer = encoder.write_header().unwrap();
+			writer
+				.write_chunk(*b"tEXt", b"Description\0# BEGIN DMI")
+				.unwrap();
+			writer.write_image_data(&pixels.concat()).unwrap();
+		}
+		bytes
+	}
+
+	///A row of distinct opaque colors.
+	fn distinct_colors(count: usize) -> Vec<[u8; 4]> {
+		(0..count)
+			.map(|index| {
+				[
+					index as u8,
+					(index / 256) as u8 * 128,
+					255 - index as u8,
+					255,
+				]
+			})
+			.collect()
+	}
+
+	fn decode(png_bytes: &[u8]) -> Vec<[u8; 4]> {
+		image::load_from_memory_with_format(png_bytes, image::ImageFormat::Png)
+			.unwrap()
+			.into_rgba8()
+			.pixels()
+			.map(|pixel| pixel.0)
+			.collect()
+	}
+
+	fn count_colors(pixels: &[[u8; 4]]) -> usize {
+		let mut colors = pixels.to_vec();
+		colors.sort_unstable();
+		colors.dedup();
+		colors.len()
+	}
+
+	///The bit depth and color type, straight out of the IHDR chunk.
+	fn header(png_bytes: &[u8]) -> (u8, u8) {
+		(png_bytes[24], png_bytes[25])
+	}
+
+	#[test]
+	fn indexed_pngs_keep_their_pixels() {
+		let mut pixels = distinct_colors(200);
+		// Fully transparent pixels come out the same, whatever their color.
+		pixels.extend_from_slice(&[[10, 20, 30, 0], [0; 4], [40, 50, 60, 128]]);
+		pixels.push([0; 4]);
+		let input = rgba_png(17, &pixels);
+		let indexed = to_indexed_png(&input, None).unwrap().unwrap();
+		assert_eq!(indexed.colors, 202);
+		assert!(!indexed.quantized);
+		assert_eq!(header(&indexed.bytes), (8, 3));
+		pixels[200] = [0; 4];
+		assert_eq!(decode(&indexed.bytes), pixels);
+		assert_eq!(
+			text_chunks(&indexed.bytes).unwrap(),
+			text_chunks(&input).unwrap()
+		);
+	}
+
+	#[test]
+	fn small_palettes_use_smaller_bit_depths() {
+		for (color_count, bit_depth) in [(2, 1), (3, 2), (4, 2), (5, 4), (16, 4), (17, 8)].iter() {
+			// An odd width, so rows end partway through a byte.
+			let pixels: Vec<[u8; 4]> = distinct_colors(*color_count)
+				.into_iter()
+				.cycle()
+				.take(7 * 3)
+				.collect();
+			let indexed = to_indexed_png(&rgba_png(7, &pixels), None)
+				.unwrap()
+				.unwrap();
+			assert_eq!(indexed.colors, *color_count);
+			assert_eq!(
+				header(&indexed.bytes),
+				(*bit_depth, 3),
+				"{} colors",
+				color_count
+			);
+			assert_eq!(decode(&indexed.bytes), pixels, "{} colors", color_count);
+		}
+	}
+
+	#[test]
+	fn too_many_colors_are_only_reduced_if_allowed() {
+		let input = rgba_png(20, &distinct_colors(300));
+		assert!(to_indexed_png(&input, None).unwrap().is_none());
+		let indexed = to_indexed_png(&input, Some(256)).unwrap().unwrap();
+		assert_eq!(indexed.colors, 256);
+		assert!(indexed.quantized);
+		assert_eq!(count_colors(&decode(&indexed.bytes)), 256);
+	}
+
+	#[test]
+	fn max_colors_limits_the_palette() {
+		let mut pixels = distinct_colors(299);
+		pixels.push([0; 4]);
+		let indexed = to_indexed_png(&rgba_png(20, &pixels), Some(16))
+			.unwrap()
+			.unwrap();
+		assert_eq!(indexed.colors, 16);
+		assert!(indexed.quantized);
+		assert_eq!(header(&indexed.bytes).0, 4);
+		let decoded = decode(&indexed.bytes);
+		assert_eq!(count_colors(&decoded), 16);
+		// Transparency keeps an entry of its own.
+		assert_eq!(decoded[299], [0; 4]);
+		assert!(decoded[..299].iter().all(|pixel| pixel[3] == 255));
+	}
+
+	#[test]
+	fn canonical_pngs_are_the_same_whatever_wrote_them() {
+		let mut pixels = distinct_colors(30);
+		pixels[3] = [1, 2, 3, 0];
+		let first = to_canonical_png(&rgba_png(5, &pixels)).unwrap();
+		pixels[3] = [0; 4];
+		let second = to_canonical_png(&rgba_png(5, &pixels)).unwrap();
+		assert_eq!(first, second);
+		assert_eq!(decode(&first), pixels);
+	}
+}
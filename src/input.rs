@@ -0,0 +1,133 @@
+use anyhow::bail;
+use anyhow::Result;
+use image::AnimationDecoder;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use super::aseprite;
+
+///The decoded input image, out of which corners and prefabs are cut.
+#[derive(Clone, Debug, Default)]
+pub struct InputSheet {
+	///One image per animation frame. Still images only hold one.
+	pub frames: Vec<image::DynamicImage>,
+	///Frames of each individual layer, by name. Only layered files have these.
+	pub layers: HashMap<String, Vec<image::DynamicImage>>,
+}
+
+impl InputSheet {
+	pub fn is_animated(&self) -> bool {
+		self.frames.len() > 1
+	}
+
+	///Returns the image holding the given frame, and the frame offset to read from that image.
+	///Still sheets hold every frame side by side, animated inputs have one whole sheet per frame.
+	pub fn frame_source(
+		&self,
+		layer: Option<&str>,
+		frame: u32,
+	) -> Result<(&image::DynamicImage, u32)> {
+		let frames = match layer {
+			Some(name) => match self.layers.get(name) {
+				Some(thing) => thing,
+				None => {
+					let mut layer_names: Vec<&String> = self.layers.keys().collect();
+					layer_names.sort();
+					bail!(
+						"Layer \"{}\" not found in the input. Available layers: {:?}",
+						name,
+						layer_names
+					)
+				}
+			},
+			None => &self.frames,
+		};
+		if frames.len() > 1 {
+			return match frames.get(frame as usize) {
+				Some(thing) => Ok((thing, 0)),
+				None => bail!(
+					"Frame {} requested, but the input only has {} animation frames",
+					frame,
+					frames.len()
+				),
+			};
+		};
+		Ok((&frames[0], frame))
+	}
+}
+
+///Decodes the input, detecting the format by its contents rather than the file extension.
+///Animated GIF and APNG files, as well as Aseprite files, are read one sheet per animation frame.
+///If a tag is given, only the Aseprite frames within it are read.
+pub fn load_input(bytes: Vec<u8>, aseprite_tag: Option<&str>) -> Result<InputSheet> {
+	if aseprite::is_aseprite(&bytes) {
+		return load_aseprite(&bytes, aseprite_tag);
+	};
+	if aseprite_tag.is_some() {
+		bail!("aseprite_tag is set, but the input is not an Aseprite file");
+	};
+	let format = image::guess_format(&bytes)?;
+	let frames = match format {
+		image::ImageFormat::Png => {
+			let decoder = image::codecs::png::PngDecoder::new(Cursor::new(&bytes))?;
+			if decoder.is_apng() {
+				collect_frames(decoder.apng().into_frames())?
+			} else {
+				vec![image::load_from_memory_with_format(&bytes, format)?]
+			}
+		}
+		image::ImageFormat::Gif => {
+			let decoder = image::codecs::gif::GifDecoder::new(Cursor::new(&bytes))?;
+			collect_frames(decoder.into_frames())?
+		}
+		_ => vec![image::load_from_memory_with_format(&bytes, format)?],
+	};
+	if frames.is_empty() {
+		bail!("The input image holds no frames");
+	};
+	Ok(InputSheet {
+		frames,
+		..Default::default()
+	})
+}
+
+fn collect_frames(frames: image::Frames) -> Result<Vec<image::DynamicImage>> {
+	let mut images = vec![];
+	for frame in frames.collect_frames()? {
+		images.push(image::DynamicImage::ImageRgba8(frame.into_buffer()));
+	}
+	Ok(images)
+}
+
+fn load_aseprite(bytes: &[u8], aseprite_tag: Option<&str>) -> Result<InputSheet> {
+	let file = aseprite::read_aseprite(bytes)?;
+	let frame_range = match aseprite_tag {
+		Some(name) => match file.tag_range(name) {
+			Some((from, to)) => from as usize..=to as usize,
+			None => bail!("Tag \"{}\" not found in the Aseprite file", name),
+		},
+		None => 0..=file.frames.len().saturating_sub(1),
+	};
+	if file.frames.is_empty() || *frame_range.end() >= file.frames.len() {
+		bail!("The Aseprite file holds no frames in the requested range");
+	};
+	let mut sheet = InputSheet::default();
+	for frame in frame_range.clone() {
+		sheet.frames.push(image::DynamicImage::ImageRgba8(
+			file.render_frame(frame, None),
+		));
+	}
+	for (index, layer) in file.layers.iter().enumerate() {
+		if layer.is_group {
+			continue;
+		};
+		let mut layer_frames = vec![];
+		for frame in frame_range.clone() {
+			layer_frames.push(image::DynamicImage::ImageRgba8(
+				file.render_frame(frame, Some(index)),
+			));
+		}
+		sheet.layers.insert(layer.name.clone(), layer_frames);
+	}
+	Ok(sheet)
+}
@@ -0,0 +1,49 @@
+use image::GenericImage;
+use image::GenericImageView;
+
+use super::glob;
+
+/*
+	* * ISOMETRIC TILES
+	* The tile is a diamond as wide as the icon. Its edges face the cardinal directions of the map, north being the upper right one,
+	* so each of its corners ends up as a smaller diamond: northwest on top, northeast to the right, southeast below and southwest to the left.
+	*/
+
+///Returns which corner of an isometric tile the given pixel belongs to.
+///Pixels above or below the diamond, such as those of walls, belong to the corner whose edge is right above or below them.
+pub fn corner_of_pixel(x: u32, y: u32, width: u32, tile_top: u32, tile_height: u32) -> u8 {
+	// Distances from the center of the diamond, so that its edges are at 1.
+	let half_width = width as f32 / 2.0;
+	let half_height = tile_height as f32 / 2.0;
+	let dx = (x as f32 + 0.5 - half_width) / half_width;
+	let dy = (y as f32 + 0.5 - tile_top as f32 - half_height) / half_height;
+	let edge = 1.0 - dx.abs();
+	let dy = dy.max(-edge).min(edge);
+	let north = dx - dy > 0.0;
+	let east = dx + dy > 0.0;
+	match (north, east) {
+		(true, true) => glob::NE_INDEX,
+		(false, true) => glob::SE_INDEX,
+		(false, false) => glob::SW_INDEX,
+		(true, false) => glob::NW_INDEX,
+	}
+}
+
+///Clears every pixel of a whole isometric tile that doesn't belong to the given corner.
+pub fn mask_corner(
+	img: &image::DynamicImage,
+	corner_dir: u8,
+	tile_top: u32,
+	tile_height: u32,
+) -> image::DynamicImage {
+	let mut corner_img = img.clone();
+	let (width, height) = img.dimensions();
+	for y in 0..height {
+		for x in 0..width {
+			if corner_of_pixel(x, y, width, tile_top, tile_height) != corner_dir {
+				corner_img.put_pixel(x, y, image::Rgba([0; 4]));
+			};
+		}
+	}
+	corner_img
+}
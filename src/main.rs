@@ -1,26 +1,44 @@
 #![allow(dead_code)]
 
 //Internal modules.
+mod aseprite;
+mod blend;
+mod cache;
 mod config;
+mod contact_sheet;
+mod explain;
 mod glob;
 mod helpers;
+mod hex;
+mod indexed;
+mod input;
+mod isometric;
+mod nine_slice;
+mod palette;
+mod preview;
+mod seams;
+mod terminal;
 
+use anyhow::bail;
 use anyhow::Result;
 use dmi::icon;
 use image::imageops;
-use std::collections::HashMap;
+use image::GenericImageView;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::Cursor;
+use std::path::Component;
 use std::path::Path;
+use std::path::PathBuf;
 
 fn main() {
 	let mut args: Vec<String> = env::args().collect();
 
 	let self_path = args.remove(0);
 
-	let prefs;
+	let mut prefs;
 	match config::load_configs(self_path.clone()) {
 		Ok(thing) => prefs = thing,
 		Err(_e) => {
@@ -30,6 +48,67 @@ fn main() {
 		}
 	};
 
+	// Options given on the command line take precedence over the config file.
+	let mut file_args = vec![];
+	for arg in args.into_iter() {
+		if !arg.starts_with("--") {
+			file_args.push(arg);
+			continue;
+		};
+		let (option, value) = match arg.find('=') {
+			Some(index) => (&arg[2..index], Some(arg[index + 1..].to_string())),
+			None => (&arg[2..], None),
+		};
+		match option {
+			"dry-run" => prefs.dry_run = true,
+			"force" => prefs.force = true,
+			"overwrite" => prefs.overwrite = true,
+			"output-dir" => match value {
+				Some(thing) => prefs.output_dir = Some(thing),
+				None => {
+					println!("--output-dir needs a folder, as in --output-dir=icons");
+					dont_disappear::any_key_to_continue::default();
+					return;
+				}
+			},
+			"preview" => prefs.preview = Some(value.unwrap_or_else(|| "all".to_string())),
+			"terminal" => {
+				let name = value.unwrap_or_else(|| "states".to_string());
+				match terminal::TerminalPreview::from_name(&name) {
+					Some(thing) => prefs.terminal_preview = Some(thing),
+					None => {
+						println!(
+							"Unlawful value for --terminal: {}. Valid ones are states and map.",
+							name
+						);
+						dont_disappear::any_key_to_continue::default();
+						return;
+					}
+				};
+			}
+			_ => {
+				println!("Unknown option: {}", arg);
+				dont_disappear::any_key_to_continue::default();
+				return;
+			}
+		};
+	}
+	let mut args = file_args;
+
+	// Checked before anything is cut, like the previews set in the config.
+	if matches!(prefs.smoothing_mode, config::SmoothingMode::Hex { .. }) {
+		if prefs.preview.is_some() {
+			println!("--preview only works for the square and isometric smoothing_modes.");
+			dont_disappear::any_key_to_continue::default();
+			return;
+		};
+		if prefs.terminal_preview == Some(terminal::TerminalPreview::Map) {
+			println!("--terminal=map only works for the square and isometric smoothing_modes.");
+			dont_disappear::any_key_to_continue::default();
+			return;
+		};
+	};
+
 	match &prefs.file_to_open {
 		Some(thing) => args.push(thing.clone()),
 		None => (),
@@ -61,16 +140,11 @@ fn main() {
 		};
 		let cursor = Cursor::new(contents);
 
-		let mut formatted_file_name = image_path_string.clone();
-
-		let dot_offset = image_path_string
-			.find('.')
-			.unwrap_or(image_path_string.len());
-		formatted_file_name = formatted_file_name.drain(..dot_offset).collect(); //Here we remove everything after the dot. Whether .dmi or .png is the same for us.
-
-		let building_return = build_icons(cursor, formatted_file_name, &prefs, icons_built);
+		let building_return = build_icons(cursor, path, &prefs, icons_built);
 		match building_return {
-			Ok(_x) => println!("Icons built successfully."),
+			Ok(true) => println!("Icons built successfully."),
+			Ok(false) if prefs.dry_run => println!("Dry run finished, nothing was written."),
+			Ok(false) => println!("Icons skipped."),
 			Err(x) => println!("Error building icon: {:#?}", x),
 		};
 		dont_disappear::any_key_to_continue::default();
@@ -83,15 +157,16 @@ fn main() {
 
 fn build_icons(
 	input: std::io::Cursor<Vec<u8>>,
-	file_string_path: String,
+	input_path: &Path,
 	prefs: &config::PrefHolder,
 	icons_built: u32,
 ) -> Result<bool> {
-	let corners_and_prefabs = prefs.build_corners_and_prefabs(input, &*file_string_path)?;
-	let corners = corners_and_prefabs.0;
-	let mounted_prefabs = corners_and_prefabs.1;
-
-	let possible_icon_states = prepare_icon_states(prefs.is_diagonal);
+	let output_path = output_base_path(prefs, input_path, icons_built);
+	let output_name = output_path.to_string_lossy().to_string();
+	let possible_icon_states = match prefs.smoothing_mode {
+		config::SmoothingMode::Hex { .. } => hex::all_signatures(),
+		_ => prepare_icon_states(prefs.is_diagonal),
+	};
 
 	let number_of_icon_states = possible_icon_states.len() as u32;
 	assert!(
@@ -100,47 +175,346 @@ fn build_icons(
 		number_of_icon_states
 	);
 	let icon_directions;
-	if prefs.produce_dirs {
+	if prefs.produce_diagonal_dirs {
+		icon_directions = glob::BYOND_ALL_DIRS.to_vec();
+	} else if prefs.produce_dirs {
 		icon_directions = glob::BYOND_CARDINALS.to_vec();
 	} else {
 		icon_directions = vec![glob::BYOND_SOUTH];
 	};
 
-	let output_name;
-	match &prefs.output_name {
-		Some(thing) => {
-			if icons_built == 0 {
-				output_name = format!("{}", &thing)
-			} else {
-				output_name = format!("{}({})", &thing, icons_built + 1)
-			};
-		}
-		None => {
-			if file_string_path.len() == 0 {
-				if icons_built == 0 {
-					output_name = "output".to_string()
-				} else {
-					output_name = format!("output({})", icons_built + 1)
-				};
-			} else {
-				output_name = format!(
-					"{}-output",
-					helpers::trim_path_before_last_slash(file_string_path.clone())
-				);
-			};
-		}
+	if prefs.dry_run {
+		let input_name = input_path.to_string_lossy();
+		explain::explain(
+			prefs,
+			input.into_inner(),
+			&input_name,
+			&output_name,
+			&possible_icon_states,
+			&icon_directions,
+		)?;
+		return Ok(false);
+	};
+
+	// Outputs are only built again if something they're built from changed since the last time.
+	let mut build_cache = None;
+	if prefs.incremental {
+		let input_hash = cache::input_hash(input.get_ref(), prefs)?;
+		let loaded_cache = cache::BuildCache::load(&output_dir(prefs));
+		if !prefs.force && loaded_cache.is_up_to_date(&output_name, input_hash) {
+			println!(
+				"{} is up to date, as nothing it's built from changed. Use --force to build it anyway.",
+				output_name
+			);
+			return Ok(false);
+		};
+		build_cache = Some((loaded_cache, input_hash));
+	};
+
+	// Files that are already there are only written over if asked to, or if they were left by the last build.
+	let target_files = target_files(prefs, &output_name);
+	for file_name in target_files.iter() {
+		if prefs.overwrite || !Path::new(file_name).exists() {
+			continue;
+		};
+		let built_last_time = match &build_cache {
+			Some((build_cache, _)) => build_cache.wrote(file_name),
+			None => false,
+		};
+		if !built_last_time {
+			bail!(
+				"{} already exists. Move it out of the way, or use --overwrite to write over it.",
+				file_name
+			);
+		};
+	}
+
+	// The map is read before anything is cut, so a mistyped one doesn't throw the whole build away.
+	// The terminal map falls back on every built-in one.
+	let preview_map = match &prefs.preview {
+		Some(map) => Some(map.as_str()),
+		None if prefs.terminal_preview == Some(terminal::TerminalPreview::Map) => Some("all"),
+		None => None,
+	};
+	let preview_mask = match preview_map {
+		Some(map) => Some(preview::load_mask(map)?),
+		None => None,
+	};
+
+	if let Some(parent) = output_path.parent() {
+		std::fs::create_dir_all(parent)?;
 	};
+	let input_name = input_path.to_string_lossy();
+	let pieces = prefs.build_corners_and_prefabs(input, &input_name, &output_name)?;
+
 	let icon_state_name;
 	icon_state_name = match &prefs.base_icon_state {
 		Some(thing) => thing.clone(),
 		None => "icon".to_string(),
 	};
 
-	let mut assembled_icons: HashMap<u8, Vec<image::DynamicImage>> = HashMap::new();
+	let mut variants: Vec<Option<&palette::Palette>> = vec![None];
+	variants.extend(prefs.palettes.iter().map(Some));
+
+	// Output file name -> icon states written into it.
+	let mut output_files: Vec<(String, Vec<icon::IconState>)> = vec![];
+	for variant in variants.iter() {
+		let recolored;
+		let variant_pieces = match variant {
+			Some(palette) => {
+				let unmatched: Vec<String> = palette
+					.unmatched_colors(&pieces)
+					.iter()
+					.map(|[red, green, blue]| format!("#{:02x}{:02x}{:02x}", red, green, blue))
+					.collect();
+				if !unmatched.is_empty() {
+					println!(
+						"Palette {} has source colors found nowhere in the input, which it leaves unchanged: {}",
+						palette.name,
+						unmatched.join(", ")
+					);
+				};
+				recolored = palette.recolor_pieces(&pieces);
+				&recolored
+			}
+			None => &pieces,
+		};
+		let variant_state_name = match variant {
+			Some(palette) if prefs.palette_states => palette.name.clone(),
+			_ => icon_state_name.clone(),
+		};
+		let variant_icon_states = assemble_icon_states(
+			prefs,
+			variant_pieces,
+			&possible_icon_states,
+			&icon_directions,
+			&variant_state_name,
+		)?;
+		match variant {
+			Some(palette) if !prefs.palette_states => output_files.push((
+				format!("{}-{}", output_name, palette.name),
+				variant_icon_states,
+			)),
+			Some(_) => output_files[0].1.extend(variant_icon_states),
+			None => output_files.push((output_name.clone(), variant_icon_states)),
+		};
+	}
+
+	// The first icon states written are those of the original colors, in the same order as their signatures.
+	let state_frames = preview::south_frames(&possible_icon_states, &output_files[0].1);
+	if let (Some(_), Some(mask)) = (&prefs.preview, &preview_mask) {
+		preview::save_preview(prefs, &state_frames, mask, &output_name)?;
+	};
+
+	if prefs.terminal_preview == Some(terminal::TerminalPreview::States) {
+		terminal::print_states(&possible_icon_states, &output_files[0].1, &icon_directions);
+	};
+
+	if prefs.produce_contact_sheet {
+		contact_sheet::save_contact_sheet(
+			prefs,
+			&possible_icon_states,
+			&output_files[0].1,
+			&icon_directions,
+			&output_name,
+		)?;
+	};
+
+	for (file_name, mut icon_states) in output_files.into_iter() {
+		if prefs.collapse_duplicates {
+			collapse_duplicates(&mut icon_states);
+		};
+		let number_of_icon_states = icon_states.len() as u32;
+		let new_icon = icon::Icon {
+			version: Default::default(),
+			width: prefs.output_icon_size_x,
+			height: prefs.output_icon_size_y,
+			states: icon_states,
+		};
+		let file_name = format!("{}.dmi", file_name);
+		let mut dmi_bytes = vec![];
+		new_icon.save(&mut dmi_bytes)?;
+		dmi_bytes = indexed::to_canonical_png(&dmi_bytes)?;
+		if prefs.indexed_output {
+			dmi_bytes = index_dmi(dmi_bytes, &file_name, prefs.max_colors)?;
+		};
+		let dmi_path = Path::new(&file_name);
+		let mut file = File::create(&dmi_path)?;
+		file.write_all(&dmi_bytes)?;
+
+		println!(
+			"{} icon states produced, with {} frames each, for a total of {} frames.",
+			number_of_icon_states,
+			prefs.frames_per_state,
+			number_of_icon_states * prefs.frames_per_state
+		);
+	}
+
+	if let Some((mut build_cache, input_hash)) = build_cache {
+		let written_files: Vec<String> = target_files
+			.into_iter()
+			.filter(|file_name| Path::new(file_name).exists())
+			.collect();
+		build_cache.record(&output_name, input_hash, &written_files)?;
+		build_cache.save()?;
+	};
+
+	// Printed once everything is written, so a map that can't be drawn doesn't cost the icons.
+	if prefs.terminal_preview == Some(terminal::TerminalPreview::Map) {
+		if let (Some(map), Some(mask)) = (preview_map, &preview_mask) {
+			match preview::render_frame(prefs, &state_frames, mask, 0) {
+				Ok(map_img) => terminal::print_map(&map_img, map),
+				Err(e) => println!("Unable to print the preview map: {}", e),
+			};
+		};
+	};
+	Ok(true)
+}
+
+///Every file a build may write, named after the output. The seams image is only written if any seams don't line up.
+fn target_files(prefs: &config::PrefHolder, output_name: &str) -> Vec<String> {
+	let mut file_names = vec![format!("{}.dmi", output_name)];
+	if !prefs.palette_states {
+		for palette in prefs.palettes.iter() {
+			file_names.push(format!("{}-{}.dmi", output_name, palette.name));
+		}
+	};
+	if prefs.produce_corners {
+		file_names.push(format!("{}-corners.png", output_name));
+	};
+	if prefs.check_seams {
+		file_names.push(format!("{}-seams.png", output_name));
+	};
+	if prefs.produce_contact_sheet {
+		file_names.push(format!("{}-states.png", output_name));
+	};
+	if prefs.preview.is_some() {
+		file_names.push(format!("{}-preview.png", output_name));
+		if prefs.frames_per_state > 1 {
+			file_names.push(format!("{}-preview.gif", output_name));
+		};
+	};
+	file_names
+}
+
+///The folder outputs are written into.
+fn output_dir(prefs: &config::PrefHolder) -> PathBuf {
+	match &prefs.output_dir {
+		Some(thing) => PathBuf::from(thing),
+		None => PathBuf::new(),
+	}
+}
+
+///Where the outputs of the given input go, without an extension, so each output can add its own suffix to it.
+fn output_base_path(prefs: &config::PrefHolder, input_path: &Path, icons_built: u32) -> PathBuf {
+	let file_stem = input_path
+		.file_stem()
+		.map(|stem| stem.to_string_lossy().to_string())
+		.unwrap_or_default();
+	let file_name = match &prefs.output_name {
+		Some(thing) if icons_built == 0 => thing.clone(),
+		Some(thing) => format!("{}({})", thing, icons_built + 1),
+		None if file_stem.is_empty() && icons_built == 0 => "output".to_string(),
+		None if file_stem.is_empty() => format!("output({})", icons_built + 1),
+		None => format!("{}-output", file_stem),
+	};
+	let mut output_path = output_dir(prefs);
+	if prefs.mirror_input_dirs {
+		if let Some(input_dir) = input_path.parent() {
+			// Inputs given with their full path are mirrored from the current folder, if they're in it.
+			let current_dir = env::current_dir().unwrap_or_default();
+			let input_dir = input_dir.strip_prefix(&current_dir).unwrap_or(input_dir);
+			for component in input_dir.components() {
+				// Only folder names are kept, so outputs can't end up outside of the output folder.
+				if let Component::Normal(dir_name) = component {
+					output_path.push(dir_name);
+				};
+			}
+		};
+	};
+	output_path.push(file_name);
+	output_path
+}
+
+///Assembles every icon state out of the cut corners and prefabs.
+fn assemble_icon_states(
+	prefs: &config::PrefHolder,
+	pieces: &config::CutPieces,
+	possible_icon_states: &[u8],
+	icon_directions: &[u8],
+	icon_state_name: &str,
+) -> Result<Vec<icon::IconState>> {
+	let assembled_icons = assemble_icons(
+		prefs,
+		&pieces.corners,
+		&pieces.prefabs,
+		&pieces.state_overlays,
+		possible_icon_states,
+	);
+	// Directions with a corner set of their own get their junctions assembled separately.
+	let mut dir_assembled_icons: BTreeMap<u8, BTreeMap<u8, Vec<image::DynamicImage>>> =
+		BTreeMap::new();
+	for (byond_dir, dir_corners) in pieces.dir_corners.iter() {
+		dir_assembled_icons.insert(
+			*byond_dir,
+			assemble_icons(
+				prefs,
+				dir_corners,
+				&pieces.prefabs,
+				&pieces.state_overlays,
+				possible_icon_states,
+			),
+		);
+	}
+
+	let mut icon_states = vec![];
+
+	for icon_signature in possible_icon_states.iter() {
+		let mut icon_state_frames = vec![];
+
+		for icon_state_dir in icon_directions.iter() {
+			let offset_signature = prefs.dir_strategies[icon_state_dir]
+				.offset_signature(*icon_signature, *icon_state_dir)?;
+			// Rotations and custom strategies can break junctions up, so the result is reduced to the icon state that would be used.
+			// Hex signatures are only ever facing south, and all of them are produced.
+			let offset_signature = match prefs.smoothing_mode {
+				config::SmoothingMode::Hex { .. } => offset_signature,
+				_ => helpers::smooth_dir_to_combination_key(offset_signature, prefs.is_diagonal),
+			};
+			let dir_icons = dir_assembled_icons
+				.get(icon_state_dir)
+				.unwrap_or(&assembled_icons);
+			icon_state_frames.extend(dir_icons[&offset_signature].clone());
+		}
+
+		let delay = prefs.delay.clone();
+
+		icon_states.push(icon::IconState {
+			name: format!("{}-{}", &icon_state_name, icon_signature),
+			dirs: icon_directions.len() as u8,
+			frames: prefs.frames_per_state,
+			images: icon_state_frames,
+			delay,
+			..Default::default()
+		})
+	}
+
+	Ok(icon_states)
+}
+
+///Assembles the images of every junction out of the given corners and prefabs, with the state overlays drawn over them.
+fn assemble_icons(
+	prefs: &config::PrefHolder,
+	corners: &config::CornerMap,
+	prefabs: &BTreeMap<u8, Vec<image::DynamicImage>>,
+	state_overlays: &[Vec<image::DynamicImage>],
+	possible_icon_states: &[u8],
+) -> BTreeMap<u8, Vec<image::DynamicImage>> {
+	let mut assembled_icons: BTreeMap<u8, Vec<image::DynamicImage>> = BTreeMap::new();
 
 	for icon_signature in possible_icon_states.iter() {
 		let mut icon_state_images = vec![];
-		if mounted_prefabs.contains_key(icon_signature) {
+		if prefabs.contains_key(icon_signature) {
 			for frame in 0..prefs.frames_per_state {
 				let mut image_frame = image::DynamicImage::new_rgba8(
 					prefs.output_icon_size_x,
@@ -148,122 +522,263 @@ fn build_icons(
 				);
 				imageops::replace(
 					&mut image_frame,
-					&mounted_prefabs[icon_signature][frame as usize],
+					&prefabs[icon_signature][frame as usize],
 					prefs.output_west_start,
 					prefs.output_north_start,
 				);
 				icon_state_images.push(image_frame);
 			}
-		} else {
+		} else if let config::SmoothingMode::NineSlice { borders, tile } = prefs.smoothing_mode {
 			for frame in 0..prefs.frames_per_state {
 				let mut image_frame = image::DynamicImage::new_rgba8(
 					prefs.output_icon_size_x,
 					prefs.output_icon_size_y,
 				);
-				let corner_img = &corners
-					.get(&glob::NW_INDEX)
-					.unwrap()
-					.get(&helpers::smooth_dir_to_corner_type(
-						glob::NW_INDEX,
-						*icon_signature,
-					))
-					.unwrap()[frame as usize];
-				imageops::overlay(
-					&mut image_frame,
-					corner_img,
-					prefs.output_west_start,
-					prefs.output_north_start,
-				);
-				let corner_img = &corners
-					.get(&glob::NE_INDEX)
-					.unwrap()
-					.get(&helpers::smooth_dir_to_corner_type(
-						glob::NE_INDEX,
-						*icon_signature,
-					))
-					.unwrap()[frame as usize];
-				imageops::overlay(
-					&mut image_frame,
-					corner_img,
-					prefs.output_east_start,
-					prefs.output_north_start,
-				);
-				let corner_img = &corners
-					.get(&glob::SE_INDEX)
-					.unwrap()
-					.get(&helpers::smooth_dir_to_corner_type(
-						glob::SE_INDEX,
-						*icon_signature,
-					))
-					.unwrap()[frame as usize];
-				imageops::overlay(
-					&mut image_frame,
-					corner_img,
-					prefs.output_east_start,
-					prefs.output_south_start,
+				for piece in glob::NINE_SLICE_PIECES.iter() {
+					let (x, y, width, height) = nine_slice::piece_rect(
+						*piece,
+						prefs.output_icon_size_x,
+						prefs.output_icon_size_y,
+						&borders,
+					);
+					if width == 0 || height == 0 {
+						continue;
+					};
+					let piece_img = &corners[piece]
+						[&nine_slice::piece_type(*piece, *icon_signature)][frame as usize];
+					let piece_img = nine_slice::fill(piece_img, width, height, tile);
+					imageops::overlay(&mut image_frame, &piece_img, x, y);
+				}
+				icon_state_images.push(image_frame);
+			}
+		} else if prefs.smoothing_mode != config::SmoothingMode::Quadrants {
+			// Each corner is a whole tile with everything else cleared, so they all go at the same spot.
+			for frame in 0..prefs.frames_per_state {
+				let mut image_frame = image::DynamicImage::new_rgba8(
+					prefs.output_icon_size_x,
+					prefs.output_icon_size_y,
 				);
-				let corner_img = &corners
-					.get(&glob::SW_INDEX)
-					.unwrap()
-					.get(&helpers::smooth_dir_to_corner_type(
-						glob::SW_INDEX,
-						*icon_signature,
-					))
-					.unwrap()[frame as usize];
-				imageops::overlay(
-					&mut image_frame,
-					corner_img,
-					prefs.output_west_start,
-					prefs.output_south_start,
+				for corner_dir in prefs.smoothing_mode.corner_dirs().iter() {
+					let corner_img = &corners[corner_dir][&prefs
+						.smoothing_mode
+						.corner_type(*corner_dir, *icon_signature)][frame as usize];
+					imageops::overlay(
+						&mut image_frame,
+						corner_img,
+						prefs.output_west_start,
+						prefs.output_north_start,
+					);
+				}
+				icon_state_images.push(image_frame);
+			}
+		} else {
+			for frame in 0..prefs.frames_per_state {
+				let mut image_frame = image::DynamicImage::new_rgba8(
+					prefs.output_icon_size_x,
+					prefs.output_icon_size_y,
 				);
+				for (order, corner_dir) in prefs.corner_draw_order.iter().enumerate() {
+					let corner_img = &corners
+						.get(corner_dir)
+						.unwrap()
+						.get(&helpers::smooth_dir_to_corner_type(
+							*corner_dir,
+							*icon_signature,
+						))
+						.unwrap()[frame as usize];
+					let (x, y, horizontal_neighbor, vertical_neighbor) = match *corner_dir {
+						glob::NE_INDEX => (
+							prefs.output_east_start,
+							prefs.output_north_start,
+							glob::NW_INDEX,
+							glob::SE_INDEX,
+						),
+						glob::SE_INDEX => (
+							prefs.output_east_start,
+							prefs.output_south_start,
+							glob::SW_INDEX,
+							glob::NE_INDEX,
+						),
+						glob::SW_INDEX => (
+							prefs.output_west_start,
+							prefs.output_south_start,
+							glob::SE_INDEX,
+							glob::NW_INDEX,
+						),
+						_ => (
+							prefs.output_west_start,
+							prefs.output_north_start,
+							glob::NE_INDEX,
+							glob::SW_INDEX,
+						),
+					};
+					if prefs.seam_feather == 0 {
+						imageops::overlay(&mut image_frame, corner_img, x, y);
+						continue;
+					};
+					// Corners fade into the neighbors drawn before them, over the seam they overlap on.
+					let drawn_before = &prefs.corner_draw_order[..order];
+					let feather_x = if drawn_before.contains(&horizontal_neighbor) {
+						prefs.seam_feather
+					} else {
+						0
+					};
+					let feather_y = if drawn_before.contains(&vertical_neighbor) {
+						prefs.seam_feather
+					} else {
+						0
+					};
+					let (west, east) = match *corner_dir {
+						glob::NE_INDEX | glob::SE_INDEX => (feather_x, 0),
+						_ => (0, feather_x),
+					};
+					let (north, south) = match *corner_dir {
+						glob::SE_INDEX | glob::SW_INDEX => (feather_y, 0),
+						_ => (0, feather_y),
+					};
+					let feathered = blend::feather_edges(corner_img, west, east, north, south);
+					imageops::overlay(&mut image_frame, &feathered, x, y);
+				}
 				icon_state_images.push(image_frame);
 			}
 		};
+		for (overlay, overlay_frames) in prefs.state_overlays.iter().zip(state_overlays.iter()) {
+			for (frame, image_frame) in icon_state_images.iter_mut().enumerate() {
+				overlay.draw(
+					image_frame,
+					&overlay_frames[frame],
+					prefs.output_west_start,
+					prefs.output_north_start,
+				);
+			}
+		}
 		assembled_icons.insert(*icon_signature, icon_state_images);
 	}
 
-	let mut icon_states = vec![];
-
-	for icon_signature in possible_icon_states.iter() {
-		let mut icon_state_frames = vec![];
+	assembled_icons
+}
 
-		for icon_state_dir in icon_directions.iter() {
-			icon_state_frames.extend(
-				assembled_icons[&helpers::dir_offset_signature(*icon_signature, *icon_state_dir)?]
-					.clone(),
-			);
-		}
+///Pairs each signature with the frames its icon state has in the dir at the given index.
+///The icon states are expected in the same order as the signatures, with their images dir by dir, as produced.
+fn state_frames<'a>(
+	signatures: &'a [u8],
+	icon_states: &'a [icon::IconState],
+	dir_index: usize,
+) -> impl Iterator<Item = (u8, &'a [image::DynamicImage])> {
+	signatures
+		.iter()
+		.zip(icon_states.iter())
+		.map(move |(signature, icon_state)| {
+			let frames = icon_state.frames as usize;
+			(
+				*signature,
+				&icon_state.images[dir_index * frames..(dir_index + 1) * frames],
+			)
+		})
+}
 
-		let delay = prefs.delay.clone();
+///Collapses states whose dirs, or frames, are all identical into a single one, and reports states identical to one another.
+fn collapse_duplicates(icon_states: &mut [icon::IconState]) {
+	let mut collapsed_dirs = 0;
+	let mut collapsed_frames = 0;
+	for icon_state in icon_states.iter_mut() {
+		let frames = icon_state.frames as usize;
+		let dirs = icon_state.dirs as usize;
+		let images = &icon_state.images;
+		if dirs > 1
+			&& (1..dirs).all(|dir| {
+				(0..frames).all(|frame| same_image(&images[dir * frames + frame], &images[frame]))
+			}) {
+			icon_state.images.truncate(frames);
+			icon_state.dirs = 1;
+			collapsed_dirs += 1;
+		};
+		let dirs = icon_state.dirs as usize;
+		let images = &icon_state.images;
+		if frames > 1
+			&& (0..dirs).all(|dir| {
+				(1..frames)
+					.all(|frame| same_image(&images[dir * frames + frame], &images[dir * frames]))
+			}) {
+			icon_state.images = images.iter().step_by(frames).cloned().collect();
+			icon_state.frames = 1;
+			icon_state.delay = None;
+			collapsed_frames += 1;
+		};
+	}
+	println!(
+		"Collapsed {} icon states into a single dir, and {} into a single frame.",
+		collapsed_dirs, collapsed_frames
+	);
 
-		icon_states.push(icon::IconState {
-			name: format!("{}-{}", &icon_state_name, icon_signature),
-			dirs: icon_directions.len() as u8,
-			frames: prefs.frames_per_state,
-			images: icon_state_frames,
-			delay,
-			..Default::default()
-		})
+	// BYOND has no way to make two states share their images, so identical ones can only be reported.
+	let mut already_matched = vec![false; icon_states.len()];
+	for (index, icon_state) in icon_states.iter().enumerate() {
+		if already_matched[index] {
+			continue;
+		};
+		let mut identical_states = vec![icon_state.name.as_str()];
+		for (other_index, other_state) in icon_states.iter().enumerate().skip(index + 1) {
+			if other_state.dirs == icon_state.dirs
+				&& other_state.frames == icon_state.frames
+				&& other_state.delay == icon_state.delay
+				&& other_state
+					.images
+					.iter()
+					.zip(icon_state.images.iter())
+					.all(|(other_image, image)| same_image(other_image, image))
+			{
+				already_matched[other_index] = true;
+				identical_states.push(other_state.name.as_str());
+			};
+		}
+		if identical_states.len() > 1 {
+			println!("Identical icon states: {}", identical_states.join(", "));
+		};
 	}
+}
 
-	let new_icon = icon::Icon {
-		version: Default::default(),
-		width: prefs.output_icon_size_x,
-		height: prefs.output_icon_size_y,
-		states: icon_states,
-	};
-	let output_name = format!("{}.dmi", output_name);
-	let dmi_path = Path::new(&output_name);
-	let mut file = File::create(&dmi_path)?;
-	new_icon.save(&mut file)?;
+fn same_image(first: &image::DynamicImage, second: &image::DynamicImage) -> bool {
+	first.dimensions() == second.dimensions() && first.as_bytes() == second.as_bytes()
+}
 
+///Converts the DMI to an indexed palette, if possible, reporting how many bytes it saves.
+fn index_dmi(dmi_bytes: Vec<u8>, file_name: &str, max_colors: Option<u32>) -> Result<Vec<u8>> {
+	let indexed_png = match indexed::to_indexed_png(&dmi_bytes, max_colors)? {
+		Some(thing) => thing,
+		None => {
+			println!(
+				"{} holds more than 256 colors, so it was left unindexed. Set max_colors to reduce them.",
+				file_name
+			);
+			return Ok(dmi_bytes);
+		}
+	};
+	if !indexed_png.quantized && indexed_png.bytes.len() >= dmi_bytes.len() {
+		println!(
+			"{} was left unindexed, as it would grow from {} to {} bytes.",
+			file_name,
+			dmi_bytes.len(),
+			indexed_png.bytes.len()
+		);
+		return Ok(dmi_bytes);
+	};
+	let saved_bytes = dmi_bytes.len() as i64 - indexed_png.bytes.len() as i64;
 	println!(
-		"{} icon states produced, with {} frames each, for a total of {} frames.",
-		number_of_icon_states,
-		prefs.frames_per_state,
-		number_of_icon_states * prefs.frames_per_state
+		"{} indexed to {} colors{}: {} bytes instead of {}, {} bytes saved ({:.1}%).",
+		file_name,
+		indexed_png.colors,
+		if indexed_png.quantized {
+			", reduced to fit"
+		} else {
+			""
+		},
+		indexed_png.bytes.len(),
+		dmi_bytes.len(),
+		saved_bytes,
+		saved_bytes as f64 * 100.0 / dmi_bytes.len() as f64
 	);
-	Ok(true)
+	Ok(indexed_png.bytes)
 }
 
 fn prepare_icon_states(is_diagonal: bool) -> Vec<u8> {
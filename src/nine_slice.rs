@@ -0,0 +1,117 @@
+use image::imageops;
+use image::GenericImageView;
+
+use super::glob;
+use super::helpers;
+
+/*
+	* * NINE-SLICE TILES
+	* The tile is cut into a 3x3 grid: four corners, four edges between them and a center.
+	* Corners keep their size, while edges and the center stretch or tile to fill icons larger (or smaller) than the cut one.
+	*/
+
+///Width of the west and east columns, and height of the north and south rows, in pixels.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct SliceBorders {
+	pub west: u32,
+	pub east: u32,
+	pub north: u32,
+	pub south: u32,
+}
+
+///Returns the rectangle (x, y, width, height) the given piece takes in a tile of the given size.
+pub fn piece_rect(
+	piece: u8,
+	width: u32,
+	height: u32,
+	borders: &SliceBorders,
+) -> (u32, u32, u32, u32) {
+	let middle_width = width - borders.west - borders.east;
+	let middle_height = height - borders.north - borders.south;
+	let west = (0, borders.west);
+	let middle_x = (borders.west, middle_width);
+	let east = (width - borders.east, borders.east);
+	let north = (0, borders.north);
+	let middle_y = (borders.north, middle_height);
+	let south = (height - borders.south, borders.south);
+	let (column, row) = match piece {
+		glob::NE_INDEX => (east, north),
+		glob::SE_INDEX => (east, south),
+		glob::SW_INDEX => (west, south),
+		glob::NW_INDEX => (west, north),
+		glob::SLICE_NORTH => (middle_x, north),
+		glob::SLICE_EAST => (east, middle_y),
+		glob::SLICE_SOUTH => (middle_x, south),
+		glob::SLICE_WEST => (west, middle_y),
+		_ => (middle_x, middle_y),
+	};
+	(column.0, row.0, column.1, row.1)
+}
+
+///Which type of the given piece to use, given the neighbors in the signature.
+///Edges are only open or closed, so they are taken from the corner before them clockwise, in its convex or straight form.
+pub fn piece_type(piece: u8, icon_signature: u8) -> u8 {
+	let open_type = |adjacency: u8, straight: u8| {
+		if icon_signature & adjacency != glob::NONE {
+			straight
+		} else {
+			glob::CONVEX
+		}
+	};
+	match piece {
+		glob::SLICE_NORTH => open_type(glob::ADJ_N, glob::VERTICAL),
+		glob::SLICE_EAST => open_type(glob::ADJ_E, glob::HORIZONTAL),
+		glob::SLICE_SOUTH => open_type(glob::ADJ_S, glob::VERTICAL),
+		glob::SLICE_WEST => open_type(glob::ADJ_W, glob::HORIZONTAL),
+		glob::SLICE_CENTER => glob::CONVEX,
+		_ => helpers::smooth_dir_to_corner_type(piece, icon_signature),
+	}
+}
+
+///Whether the given piece is ever of the given type.
+pub fn piece_has_type(piece: u8, corner_type: u8) -> bool {
+	match piece {
+		glob::SLICE_NORTH | glob::SLICE_SOUTH => {
+			corner_type == glob::CONVEX || corner_type == glob::VERTICAL
+		}
+		glob::SLICE_EAST | glob::SLICE_WEST => {
+			corner_type == glob::CONVEX || corner_type == glob::HORIZONTAL
+		}
+		glob::SLICE_CENTER => corner_type == glob::CONVEX,
+		_ => true,
+	}
+}
+
+///Returns the corner whose sources the given piece is cut out of.
+pub fn source_corner(piece: u8) -> u8 {
+	match piece {
+		glob::SLICE_NORTH | glob::SLICE_CENTER => glob::NE_INDEX,
+		glob::SLICE_EAST => glob::SE_INDEX,
+		glob::SLICE_SOUTH => glob::SW_INDEX,
+		glob::SLICE_WEST => glob::NW_INDEX,
+		_ => piece,
+	}
+}
+
+///Stretches or tiles the piece to the given size. Tiled pieces start at their top-left corner and are cut short at the far edges.
+pub fn fill(img: &image::DynamicImage, width: u32, height: u32, tile: bool) -> image::DynamicImage {
+	if img.dimensions() == (width, height) {
+		return img.clone();
+	};
+	if !tile {
+		// Nearest neighbor, so pixel art stays sharp.
+		return img.resize_exact(width, height, imageops::FilterType::Nearest);
+	};
+	let mut filled = image::DynamicImage::new_rgba8(width, height);
+	let (piece_width, piece_height) = img.dimensions();
+	let mut y = 0;
+	while y < height {
+		let mut x = 0;
+		while x < width {
+			imageops::replace(&mut filled, img, x, y);
+			x += piece_width;
+		}
+		y += piece_height;
+	}
+	filled
+}
@@ -0,0 +1,135 @@
+use anyhow::bail;
+use anyhow::Result;
+use image::GenericImage;
+use image::GenericImageView;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use super::config;
+
+///A named set of color replacements, used to produce recolored variants of the same icons.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Palette {
+	pub name: String,
+	///Source color -> replacement color. Only red, green and blue are compared and replaced, the alpha of each pixel is kept.
+	pub colors: BTreeMap<[u8; 3], [u8; 3]>,
+}
+
+impl Palette {
+	///Reads the replacements out of a palette image. The first row holds the source colors, and the given row their replacements.
+	///Fully transparent pixels in either row are skipped.
+	pub fn from_image(name: &str, img: &image::DynamicImage, row: u32) -> Result<Palette> {
+		let (width, height) = img.dimensions();
+		if row == 0 || row >= height {
+			bail!(
+				"Unlawful row for palette {} ({}), the palette image is {} pixels tall and the first row holds the source colors",
+				name,
+				row,
+				height
+			);
+		};
+		let mut colors = BTreeMap::new();
+		for x in 0..width {
+			let source = img.get_pixel(x, 0);
+			let target = img.get_pixel(x, row);
+			if source[3] == 0 || target[3] == 0 {
+				continue;
+			};
+			colors.insert(
+				[source[0], source[1], source[2]],
+				[target[0], target[1], target[2]],
+			);
+		}
+		Ok(Palette {
+			name: name.to_string(),
+			colors,
+		})
+	}
+
+	pub fn recolor(&self, img: &image::DynamicImage) -> image::DynamicImage {
+		let mut recolored = img.clone();
+		let (width, height) = img.dimensions();
+		for y in 0..height {
+			for x in 0..width {
+				let mut pixel = img.get_pixel(x, y);
+				if pixel[3] == 0 {
+					continue;
+				};
+				if let Some(target) = self.colors.get(&[pixel[0], pixel[1], pixel[2]]) {
+					pixel[0] = target[0];
+					pixel[1] = target[1];
+					pixel[2] = target[2];
+					recolored.put_pixel(x, y, pixel);
+				};
+			}
+		}
+		recolored
+	}
+
+	pub fn recolor_frames(&self, frames: &[image::DynamicImage]) -> Vec<image::DynamicImage> {
+		frames.iter().map(|frame| self.recolor(frame)).collect()
+	}
+
+	pub fn recolor_corners(&self, corners: &config::CornerMap) -> config::CornerMap {
+		corners
+			.iter()
+			.map(|(corner_dir, dir_map)| {
+				let dir_map = dir_map
+					.iter()
+					.map(|(corner_type, frames)| (*corner_type, self.recolor_frames(frames)))
+					.collect();
+				(*corner_dir, dir_map)
+			})
+			.collect()
+	}
+
+	///The source colors found in none of the cut corners and prefabs, which this palette leaves as they are.
+	pub fn unmatched_colors(&self, pieces: &config::CutPieces) -> Vec<[u8; 3]> {
+		let mut unmatched: BTreeSet<[u8; 3]> = self.colors.keys().copied().collect();
+		let corner_frames = pieces
+			.corners
+			.values()
+			.chain(
+				pieces
+					.dir_corners
+					.values()
+					.flat_map(|corners| corners.values()),
+			)
+			.flat_map(|dir_map| dir_map.values());
+		for frames in corner_frames.chain(pieces.prefabs.values()) {
+			for frame in frames.iter() {
+				for (_x, _y, pixel) in frame.pixels() {
+					if pixel[3] != 0 {
+						unmatched.remove(&[pixel[0], pixel[1], pixel[2]]);
+					};
+				}
+				if unmatched.is_empty() {
+					return vec![];
+				};
+			}
+		}
+		unmatched.into_iter().collect()
+	}
+
+	///Recolors every cut corner and prefab, so the variant can be assembled without cutting the input again.
+	///State overlays are left as they are, as they are drawn over the recolored icons.
+	pub fn recolor_pieces(&self, pieces: &config::CutPieces) -> config::CutPieces {
+		let corners = self.recolor_corners(&pieces.corners);
+		let dir_corners = pieces
+			.dir_corners
+			.iter()
+			.map(|(byond_dir, corners)| (*byond_dir, self.recolor_corners(corners)))
+			.collect();
+		let prefabs = pieces
+			.prefabs
+			.iter()
+			.map(|(signature, frames)| (*signature, self.recolor_frames(frames)))
+			.collect();
+		config::CutPieces {
+			corners,
+			dir_corners,
+			prefabs,
+			state_overlays: pieces.state_overlays.clone(),
+		}
+	}
+}
@@ -0,0 +1,275 @@
+use anyhow::bail;
+use anyhow::Result;
+use image::GenericImageView;
+use std::collections::BTreeMap;
+
+use super::config;
+use super::glob;
+use super::helpers;
+
+/*
+	* * PREVIEW MAPS
+	* Sample maps drawn with the produced icon states, picked by each tile's neighbors just as the game would.
+	* In the ASCII maps any character other than a space or a dot is a tile.
+	*/
+
+const ROOMS: &str = "\
+##########
+#........#
+#........#
+#...######
+#...#
+#...#######
+#.........#
+###########";
+
+const CORRIDORS: &str = "\
+#############
+.............
+#############
+.....#.#.....
+.....#.#.....
+.....#.#.....
+######.######
+.............
+#############";
+
+const BLOBS: &str = "\
+...##.....
+..####....
+.######...
+########..
+.#######..
+..#####.#.
+...###.###
+....#...#.";
+
+const PILLARS: &str = "\
+#...#...#
+.........
+..#...#..
+.........
+#...#...#";
+
+pub const BUILTIN_MAPS: [(&str, &str); 4] = [
+	("rooms", ROOMS),
+	("corridors", CORRIDORS),
+	("blobs", BLOBS),
+	("pillars", PILLARS),
+];
+
+///Which cells of the map hold a tile, row by row.
+pub type Mask = Vec<Vec<bool>>;
+
+///Where the icon of a map cell is drawn, from its column and row.
+type CellPosition = Box<dyn Fn(u32, u32) -> (u32, u32)>;
+
+///Loads the map to preview: "all" for every built-in map side by side, the name of one of them, or the path of an ASCII or image mask.
+///In image masks, opaque dark pixels are tiles.
+pub fn load_mask(map: &str) -> Result<Mask> {
+	if map == "all" {
+		let masks: Vec<Mask> = BUILTIN_MAPS
+			.iter()
+			.map(|(_, ascii)| parse_ascii(ascii))
+			.collect();
+		return Ok(side_by_side(&masks));
+	};
+	if let Some((_, ascii)) = BUILTIN_MAPS.iter().find(|(name, _)| *name == map) {
+		return Ok(parse_ascii(ascii));
+	};
+	let bytes = match std::fs::read(map) {
+		Ok(thing) => thing,
+		Err(e) => bail!(
+			"Unable to read preview map \"{}\", which isn't one of the built-in ones either ({}): {}",
+			map,
+			BUILTIN_MAPS
+				.iter()
+				.map(|(name, _)| *name)
+				.collect::<Vec<&str>>()
+				.join(", "),
+			e
+		),
+	};
+	if image::guess_format(&bytes).is_ok() {
+		let img = image::load_from_memory(&bytes)?;
+		let (width, height) = img.dimensions();
+		let mask = (0..height)
+			.map(|y| {
+				(0..width)
+					.map(|x| {
+						let pixel = img.get_pixel(x, y);
+						let brightness = (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3;
+						pixel[3] >= 128 && brightness < 128
+					})
+					.collect()
+			})
+			.collect();
+		return Ok(mask);
+	};
+	match String::from_utf8(bytes) {
+		Ok(text) => Ok(parse_ascii(&text)),
+		Err(_) => bail!(
+			"Preview map \"{}\" is neither an image nor a text file",
+			map
+		),
+	}
+}
+
+fn parse_ascii(ascii: &str) -> Mask {
+	let mut mask: Mask = ascii
+		.lines()
+		.map(|line| {
+			line.trim_end_matches('\r')
+				.chars()
+				.map(|character| character != ' ' && character != '.')
+				.collect()
+		})
+		.collect();
+	let width = mask.iter().map(|row| row.len()).max().unwrap_or(0);
+	for row in mask.iter_mut() {
+		row.resize(width, false);
+	}
+	mask
+}
+
+///Joins the masks from left to right, with an empty column between each.
+fn side_by_side(masks: &[Mask]) -> Mask {
+	let height = masks.iter().map(|mask| mask.len()).max().unwrap_or(0);
+	let mut joined: Mask = vec![vec![]; height];
+	for (index, mask) in masks.iter().enumerate() {
+		let width = mask.first().map_or(0, |row| row.len());
+		for (y, row) in joined.iter_mut().enumerate() {
+			if index > 0 {
+				row.push(false);
+			};
+			match mask.get(y) {
+				Some(mask_row) => row.extend(mask_row),
+				None => row.resize(row.len() + width, false),
+			};
+		}
+	}
+	joined
+}
+
+///Returns the junction signature of the tile at the given cell, out of the tiles around it.
+pub fn signature_at(mask: &Mask, x: usize, y: usize) -> u8 {
+	let mut signature = glob::NONE;
+	for (adjacency, offset_x, offset_y) in glob::ADJ_OFFSETS.iter() {
+		let neighbor_x = x as i32 + offset_x;
+		let neighbor_y = y as i32 + offset_y;
+		if neighbor_x < 0 || neighbor_y < 0 {
+			continue;
+		};
+		let filled = mask
+			.get(neighbor_y as usize)
+			.and_then(|row| row.get(neighbor_x as usize))
+			.copied()
+			.unwrap_or(false);
+		if filled {
+			signature |= adjacency;
+		};
+	}
+	signature
+}
+
+///Draws the map with the given frame of each icon state, by junction signature.
+pub fn render_frame(
+	prefs: &config::PrefHolder,
+	state_frames: &BTreeMap<u8, Vec<image::DynamicImage>>,
+	mask: &Mask,
+	frame: usize,
+) -> Result<image::DynamicImage> {
+	let rows = mask.len() as u32;
+	let columns = mask.first().map_or(0, |row| row.len()) as u32;
+	let icon_width = prefs.output_icon_size_x;
+	let icon_height = prefs.output_icon_size_y;
+	// Where each cell's icon goes, and how big the whole map is.
+	let (canvas_width, canvas_height, position): (u32, u32, CellPosition) =
+		match prefs.smoothing_mode {
+			config::SmoothingMode::Hex { .. } => {
+				bail!("Preview maps are only drawn for square and isometric tiles")
+			}
+			config::SmoothingMode::Isometric { tile_height, .. } => {
+				// East is down and to the right, south down and to the left.
+				let half_width = icon_width / 2;
+				let half_height = tile_height / 2;
+				(
+					(columns + rows) * half_width,
+					(columns + rows).saturating_sub(2) * half_height + icon_height,
+					Box::new(move |x, y| ((x + rows - 1 - y) * half_width, (x + y) * half_height)),
+				)
+			}
+			_ => (
+				columns * icon_width,
+				rows * icon_height,
+				Box::new(move |x, y| (x * icon_width, y * icon_height)),
+			),
+		};
+	let mut cells = vec![];
+	for y in 0..rows {
+		for x in 0..columns {
+			if mask[y as usize][x as usize] {
+				cells.push((x, y));
+			};
+		}
+	}
+	// Back to front, so taller icons are drawn over the ones behind them.
+	cells.sort_by_key(|(x, y)| {
+		let (screen_x, screen_y) = position(*x, *y);
+		(screen_y, screen_x)
+	});
+	let mut canvas = image::DynamicImage::new_rgba8(canvas_width, canvas_height);
+	for (x, y) in cells.iter() {
+		let signature = signature_at(mask, *x as usize, *y as usize);
+		let signature = helpers::smooth_dir_to_combination_key(signature, prefs.is_diagonal);
+		let frames = match state_frames.get(&signature) {
+			Some(thing) => thing,
+			None => bail!("No icon state produced for signature {}", signature),
+		};
+		let (screen_x, screen_y) = position(*x, *y);
+		image::imageops::overlay(&mut canvas, &frames[frame], screen_x, screen_y);
+	}
+	Ok(canvas)
+}
+
+///Returns the south-facing frames of each icon state, by signature.
+pub fn south_frames(
+	signatures: &[u8],
+	icon_states: &[dmi::icon::IconState],
+) -> BTreeMap<u8, Vec<image::DynamicImage>> {
+	super::state_frames(signatures, icon_states, 0)
+		.map(|(signature, frames)| (signature, frames.to_vec()))
+		.collect()
+}
+
+///Saves the preview map as a PNG, and as a GIF as well if the icons are animated.
+pub fn save_preview(
+	prefs: &config::PrefHolder,
+	state_frames: &BTreeMap<u8, Vec<image::DynamicImage>>,
+	mask: &Mask,
+	output_name: &str,
+) -> Result<()> {
+	let png_name = format!("{}-preview.png", output_name);
+	render_frame(prefs, state_frames, mask, 0)?.save(&png_name)?;
+	println!("Preview map saved as {}.", png_name);
+	if prefs.frames_per_state == 1 {
+		return Ok(());
+	};
+	let mut gif_frames = vec![];
+	for frame in 0..prefs.frames_per_state as usize {
+		// Delays are in ticks, a tenth of a second each.
+		let delay = match &prefs.delay {
+			Some(delays) => delays[frame],
+			None => 1.0,
+		};
+		let delay = image::Delay::from_numer_denom_ms((delay * 100.0).round() as u32, 1);
+		let canvas = render_frame(prefs, state_frames, mask, frame)?;
+		gif_frames.push(image::Frame::from_parts(canvas.into_rgba8(), 0, 0, delay));
+	}
+	let gif_name = format!("{}-preview.gif", output_name);
+	let gif_file = std::fs::File::create(&gif_name)?;
+	let mut encoder = image::codecs::gif::GifEncoder::new(gif_file);
+	encoder.encode_frames(gif_frames)?;
+	println!("Animated preview map saved as {}.", gif_name);
+	Ok(())
+}
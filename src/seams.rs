@@ -0,0 +1,269 @@
+use anyhow::Result;
+use image::GenericImage;
+use image::GenericImageView;
+
+use super::config;
+use super::glob;
+use super::helpers;
+
+/*
+	* * SEAM CHECKER
+	* Finds the corners that can end up next to each other, within a tile or across neighboring ones,
+	* and compares the pixels on both sides of the line they meet at.
+	*/
+
+const HIGHLIGHT: image::Rgba<u8> = image::Rgba([255, 0, 255, 255]);
+
+///Two corners that can meet. The first is west of the second, or north of it if vertical.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct SeamPair {
+	first: (u8, u8),
+	second: (u8, u8),
+	vertical: bool,
+	across_tiles: bool,
+}
+
+///Tiles and the corners of theirs that meet, by the index of the tile: (first tile, its corner, second tile, its corner, vertical).
+type Seam = (usize, u8, usize, u8, bool);
+
+///Compares every pair of corners that can meet and reports the pixels that don't line up, saving them highlighted into an image.
+///Returns the number of mismatched pixels.
+pub fn check_seams(
+	prefs: &config::PrefHolder,
+	corners: &config::CornerMap,
+	output_name: &str,
+) -> Result<u32> {
+	let mut pairs = vec![];
+	// A lone tile and its eight possible neighbors.
+	collect_pairs(
+		&[(0, 0)],
+		&[
+			(0, glob::NW_INDEX, 0, glob::NE_INDEX, false),
+			(0, glob::SW_INDEX, 0, glob::SE_INDEX, false),
+			(0, glob::NW_INDEX, 0, glob::SW_INDEX, true),
+			(0, glob::NE_INDEX, 0, glob::SE_INDEX, true),
+		],
+		false,
+		prefs.is_diagonal,
+		&mut pairs,
+	);
+	// Two tiles side by side, and two stacked.
+	collect_pairs(
+		&[(0, 0), (1, 0)],
+		&[
+			(0, glob::NE_INDEX, 1, glob::NW_INDEX, false),
+			(0, glob::SE_INDEX, 1, glob::SW_INDEX, false),
+		],
+		true,
+		prefs.is_diagonal,
+		&mut pairs,
+	);
+	collect_pairs(
+		&[(0, 0), (0, 1)],
+		&[
+			(0, glob::SW_INDEX, 1, glob::NW_INDEX, true),
+			(0, glob::SE_INDEX, 1, glob::NE_INDEX, true),
+		],
+		true,
+		prefs.is_diagonal,
+		&mut pairs,
+	);
+
+	let mut total_mismatches = 0;
+	let mut debug_rows = vec![];
+	for pair in pairs.iter() {
+		for frame in 0..prefs.frames_per_state as usize {
+			let first = &corners[&pair.first.0][&pair.first.1][frame];
+			let second = &corners[&pair.second.0][&pair.second.1][frame];
+			let mismatches = compare_edges(first, second, pair.vertical, prefs.seam_tolerance);
+			if mismatches.is_empty() {
+				continue;
+			};
+			total_mismatches += mismatches.len() as u32;
+			let (first_name, _) = prefs.get_corner_source(pair.first.0, pair.first.1)?;
+			let (second_name, _) = prefs.get_corner_source(pair.second.0, pair.second.1)?;
+			println!(
+				"Seam mismatch between {} and {} ({}, {}), frame {}: {} pixels, at {} {:?}.",
+				first_name,
+				second_name,
+				if pair.vertical {
+					"north to south"
+				} else {
+					"west to east"
+				},
+				if pair.across_tiles {
+					"across tiles"
+				} else {
+					"within a tile"
+				},
+				frame,
+				mismatches.len(),
+				if pair.vertical { "x" } else { "y" },
+				mismatches
+			);
+			debug_rows.push(draw_pair(first, second, pair.vertical, &mismatches));
+		}
+	}
+
+	if debug_rows.is_empty() {
+		println!("No seam mismatches found.");
+		return Ok(0);
+	};
+	let width = debug_rows.iter().map(|row| row.width()).max().unwrap();
+	// A pixel of space between each pair.
+	let height = debug_rows.iter().map(|row| row.height() + 1).sum::<u32>() - 1;
+	let mut debug_image = image::DynamicImage::new_rgba8(width, height);
+	let mut y = 0;
+	for row in debug_rows.iter() {
+		image::imageops::replace(&mut debug_image, row, 0, y);
+		y += row.height() + 1;
+	}
+	let debug_name = format!("{}-seams.png", output_name);
+	debug_image.save(&debug_name)?;
+	println!(
+		"{} mismatched seam pixels found, highlighted in {}.",
+		total_mismatches, debug_name
+	);
+	Ok(total_mismatches)
+}
+
+///Lays out every combination of neighbors around the given tiles, and adds the corners each seam would join to the pairs, once each.
+fn collect_pairs(
+	tiles: &[(i32, i32)],
+	seams: &[Seam],
+	across_tiles: bool,
+	is_diagonal: bool,
+	pairs: &mut Vec<SeamPair>,
+) {
+	let mut neighbors: Vec<(i32, i32)> = vec![];
+	for (tile_x, tile_y) in tiles.iter() {
+		for (_, offset_x, offset_y) in glob::ADJ_OFFSETS.iter() {
+			let cell = (tile_x + offset_x, tile_y + offset_y);
+			if !tiles.contains(&cell) && !neighbors.contains(&cell) {
+				neighbors.push(cell);
+			};
+		}
+	}
+	for occupied_bits in 0..(1u32 << neighbors.len()) {
+		let occupied = |cell: (i32, i32)| -> bool {
+			if tiles.contains(&cell) {
+				return true;
+			};
+			match neighbors.iter().position(|neighbor| *neighbor == cell) {
+				Some(index) => occupied_bits & (1 << index) != 0,
+				None => false,
+			}
+		};
+		let signatures: Vec<u8> = tiles
+			.iter()
+			.map(|(tile_x, tile_y)| {
+				let mut signature = glob::NONE;
+				for (adjacency, offset_x, offset_y) in glob::ADJ_OFFSETS.iter() {
+					if occupied((tile_x + offset_x, tile_y + offset_y)) {
+						signature |= adjacency;
+					};
+				}
+				helpers::smooth_dir_to_combination_key(signature, is_diagonal)
+			})
+			.collect();
+		for (first_tile, first_dir, second_tile, second_dir, vertical) in seams.iter() {
+			let pair = SeamPair {
+				first: (
+					*first_dir,
+					helpers::smooth_dir_to_corner_type(*first_dir, signatures[*first_tile]),
+				),
+				second: (
+					*second_dir,
+					helpers::smooth_dir_to_corner_type(*second_dir, signatures[*second_tile]),
+				),
+				vertical: *vertical,
+				across_tiles,
+			};
+			if !pairs.contains(&pair) {
+				pairs.push(pair);
+			};
+		}
+	}
+}
+
+///Returns where, along the line two corners meet, the pixels on either side of it don't match.
+///Pixels mismatch if only one of them is transparent, or if any of their channels differ by more than the tolerance.
+fn compare_edges(
+	first: &image::DynamicImage,
+	second: &image::DynamicImage,
+	vertical: bool,
+	tolerance: u8,
+) -> Vec<u32> {
+	let mut mismatches = vec![];
+	let length = if vertical {
+		first.width().min(second.width())
+	} else {
+		first.height().min(second.height())
+	};
+	for position in 0..length {
+		let (first_pixel, second_pixel) = if vertical {
+			(
+				first.get_pixel(position, first.height() - 1),
+				second.get_pixel(position, 0),
+			)
+		} else {
+			(
+				first.get_pixel(first.width() - 1, position),
+				second.get_pixel(0, position),
+			)
+		};
+		let first_visible = first_pixel[3] != 0;
+		let second_visible = second_pixel[3] != 0;
+		let mismatched = if first_visible != second_visible {
+			true
+		} else if !first_visible {
+			false
+		} else {
+			(0..4).any(|channel| {
+				(first_pixel[channel] as i32 - second_pixel[channel] as i32).abs()
+					> tolerance as i32
+			})
+		};
+		if mismatched {
+			mismatches.push(position);
+		};
+	}
+	mismatches
+}
+
+///Draws the two corners as they meet, with the mismatched pixels on both sides of the line highlighted.
+fn draw_pair(
+	first: &image::DynamicImage,
+	second: &image::DynamicImage,
+	vertical: bool,
+	mismatches: &[u32],
+) -> image::DynamicImage {
+	let (width, height, second_x, second_y) = if vertical {
+		(
+			first.width().max(second.width()),
+			first.height() + second.height(),
+			0,
+			first.height(),
+		)
+	} else {
+		(
+			first.width() + second.width(),
+			first.height().max(second.height()),
+			first.width(),
+			0,
+		)
+	};
+	let mut pair_image = image::DynamicImage::new_rgba8(width, height);
+	image::imageops::replace(&mut pair_image, first, 0, 0);
+	image::imageops::replace(&mut pair_image, second, second_x, second_y);
+	for position in mismatches.iter() {
+		if vertical {
+			pair_image.put_pixel(*position, second_y - 1, HIGHLIGHT);
+			pair_image.put_pixel(*position, second_y, HIGHLIGHT);
+		} else {
+			pair_image.put_pixel(second_x - 1, *position, HIGHLIGHT);
+			pair_image.put_pixel(second_x, *position, HIGHLIGHT);
+		};
+	}
+	pair_image
+}
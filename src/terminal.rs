@@ -0,0 +1,173 @@
+use image::GenericImageView;
+use std::fmt::Write;
+
+use super::config;
+
+/*
+	* * TERMINAL PREVIEW
+	* Images are printed with truecolor ANSI escapes, two pixels per character: the upper half block takes the color of the top pixel,
+	* and the background that of the bottom one. Mostly transparent pixels are left to the terminal's own background.
+	*/
+
+///What gets printed to the terminal once the icons are built.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TerminalPreview {
+	///Every produced icon state, labeled with its signature, a block per dir.
+	States,
+	///The preview map, as drawn by preview::render_frame.
+	Map,
+}
+
+impl TerminalPreview {
+	pub fn from_name(name: &str) -> Option<TerminalPreview> {
+		match name {
+			"states" => Some(TerminalPreview::States),
+			"map" => Some(TerminalPreview::Map),
+			_ => None,
+		}
+	}
+}
+
+const UPPER_HALF: char = '\u{2580}';
+const LOWER_HALF: char = '\u{2584}';
+const RESET: &str = "\x1b[0m";
+//Pixels less opaque than this are treated as transparent.
+const ALPHA_THRESHOLD: u8 = 128;
+
+///Width of the terminal in characters, out of the COLUMNS environment variable if set.
+pub fn terminal_width() -> u32 {
+	std::env::var("COLUMNS")
+		.ok()
+		.and_then(|columns| columns.parse().ok())
+		.filter(|columns| *columns > 0)
+		.unwrap_or(120)
+}
+
+fn pixel_at(img: &image::DynamicImage, x: u32, y: u32) -> Option<image::Rgba<u8>> {
+	if y >= img.height() {
+		return None;
+	};
+	let pixel = img.get_pixel(x, y);
+	if pixel[3] < ALPHA_THRESHOLD {
+		None
+	} else {
+		Some(pixel)
+	}
+}
+
+///Returns the given row of characters of the image, two pixel rows per character, without a line break.
+fn render_row(img: &image::DynamicImage, row: u32) -> String {
+	let mut line = String::new();
+	for x in 0..img.width() {
+		let top = pixel_at(img, x, row * 2);
+		let bottom = pixel_at(img, x, row * 2 + 1);
+		// Writing to a String can't fail.
+		let _ = match (top, bottom) {
+			(None, None) => write!(line, "{} ", RESET),
+			(Some(top), None) => write!(
+				line,
+				"{}\x1b[38;2;{};{};{}m{}",
+				RESET, top[0], top[1], top[2], UPPER_HALF
+			),
+			(None, Some(bottom)) => write!(
+				line,
+				"{}\x1b[38;2;{};{};{}m{}",
+				RESET, bottom[0], bottom[1], bottom[2], LOWER_HALF
+			),
+			(Some(top), Some(bottom)) => write!(
+				line,
+				"\x1b[38;2;{};{};{};48;2;{};{};{}m{}",
+				top[0], top[1], top[2], bottom[0], bottom[1], bottom[2], UPPER_HALF
+			),
+		};
+	}
+	line.push_str(RESET);
+	line
+}
+
+///Shrinks the image down to the given width, if wider, keeping its proportions.
+fn fit_width(img: &image::DynamicImage, width: u32) -> image::DynamicImage {
+	if img.width() <= width {
+		return img.clone();
+	};
+	let height = (img.height() as u64 * width as u64 / img.width() as u64).max(1) as u32;
+	img.resize_exact(width, height, image::imageops::FilterType::Nearest)
+}
+
+///Prints the image, shrunk down to fit the terminal if needed.
+pub fn print_image(img: &image::DynamicImage) {
+	let img = fit_width(img, terminal_width());
+	for row in 0..(img.height() + 1) / 2 {
+		println!("{}", render_row(&img, row));
+	}
+}
+
+///Prints the images side by side, as many to a line as fit in the terminal, each under its label.
+///Labels longer than the images are cut short.
+pub fn print_labeled_images(images: &[(String, &image::DynamicImage)]) {
+	let width = terminal_width();
+	let mut start = 0;
+	while start < images.len() {
+		// At least one image per line, shrunk down if even that one doesn't fit.
+		let mut end = start + 1;
+		let mut line_width = images[start].1.width();
+		while end < images.len() && line_width + 1 + images[end].1.width() <= width {
+			line_width += 1 + images[end].1.width();
+			end += 1;
+		}
+		let line_images: Vec<image::DynamicImage> = images[start..end]
+			.iter()
+			.map(|(_, img)| fit_width(img, width))
+			.collect();
+		let labels: Vec<String> = images[start..end]
+			.iter()
+			.zip(line_images.iter())
+			.map(|((label, _), img)| {
+				let label: String = label.chars().take(img.width() as usize).collect();
+				format!("{:width$}", label, width = img.width() as usize)
+			})
+			.collect();
+		println!("{}", labels.join(" "));
+		let rows = line_images
+			.iter()
+			.map(|img| (img.height() + 1) / 2)
+			.max()
+			.unwrap_or(0);
+		for row in 0..rows {
+			let line: Vec<String> = line_images
+				.iter()
+				.map(|img| {
+					if row < (img.height() + 1) / 2 {
+						render_row(img, row)
+					} else {
+						" ".repeat(img.width() as usize)
+					}
+				})
+				.collect();
+			println!("{}", line.join(" "));
+		}
+		start = end;
+	}
+}
+
+///Prints the first frame of every icon state, labeled with its signature, a block per dir.
+pub fn print_states(
+	signatures: &[u8],
+	icon_states: &[dmi::icon::IconState],
+	icon_directions: &[u8],
+) {
+	for (dir_index, byond_dir) in icon_directions.iter().enumerate() {
+		println!("== {} ==", config::byond_dir_name(*byond_dir));
+		let labeled: Vec<(String, &image::DynamicImage)> =
+			super::state_frames(signatures, icon_states, dir_index)
+				.map(|(signature, frames)| (signature.to_string(), &frames[0]))
+				.collect();
+		print_labeled_images(&labeled);
+	}
+}
+
+///Prints the preview map, drawn with the first frame of each state.
+pub fn print_map(map_img: &image::DynamicImage, map_name: &str) {
+	println!("== preview map: {} ==", map_name);
+	print_image(map_img);
+}
@@ -0,0 +1,500 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+/*
+	* * GOLDEN IMAGE TESTS
+	* Every example under examples/ is run through the cutter, and the DMIs it produces are compared against the ones checked in
+	* under tests/golden/<example>/, by their pixels and metadata rather than their raw bytes.
+	* Frames are compared in the order the metadata lists them, wherever the DMI writer placed them in the sheet.
+	* When the pixels differ, an image with the golden icon, the produced one and the differing pixels side by side is written
+	* under the target directory, and its path reported.
+	* Once the differences are checked to be intended, the produced DMIs are blessed as the new goldens with:
+	* BLESS_GOLDENS=1 cargo test --test golden
+	*/
+
+const BLESS_VAR: &str = "BLESS_GOLDENS";
+const BLESS_COMMAND: &str = "BLESS_GOLDENS=1 cargo test --test golden";
+
+const DIFF_COLOR: image::Rgba<u8> = image::Rgba([255, 0, 0, 255]);
+const DIFF_GAP: u32 = 4;
+
+///The parts of a DMI that matter: its frames, laid out anew in a square grid, and the metadata describing its icon states.
+struct Dmi {
+	image: image::RgbaImage,
+	description: String,
+}
+
+///Returns the icon width and height, and how many frames the icon states hold in total, dirs included.
+fn sheet_layout(lines: &[String]) -> Result<(u32, u32, u32), String> {
+	let (mut width, mut height) = (None, None);
+	// Dirs and frames of each icon state, in order.
+	let mut states: Vec<(u32, u32)> = vec![];
+	for line in lines.iter() {
+		let (key, value) = match line.find(" = ") {
+			Some(index) => (&line[..index], &line[index + 3..]),
+			None => continue,
+		};
+		let number = value.parse::<u32>().ok();
+		match (key, states.last_mut()) {
+			("state", _) => states.push((1, 1)),
+			("width", None) => width = number,
+			("height", None) => height = number,
+			("dirs", Some(state)) => state.0 = number.ok_or("unreadable dirs")?,
+			("frames", Some(state)) => state.1 = number.ok_or("unreadable frames")?,
+			_ => (),
+		};
+	}
+	match (width, height) {
+		(Some(width), Some(height)) if width > 0 && height > 0 => Ok((
+			width,
+			height,
+			states.iter().map(|(dirs, frames)| dirs * frames).sum(),
+		)),
+		_ => Err("no icon size in the metadata".to_string()),
+	}
+}
+
+///Decodes a DMI, which is a PNG file with its metadata in a "Description" text chunk.
+///Fully transparent pixels all look the same, whatever their color channels say, so they are cleared.
+fn read_dmi(bytes: &[u8]) -> Result<Dmi, String> {
+	let mut image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+		.map_err(|e| format!("not a PNG file: {}", e))?
+		.into_rgba8();
+	for pixel in image.pixels_mut() {
+		if pixel[3] == 0 {
+			*pixel = image::Rgba([0; 4]);
+		};
+	}
+
+	let mut description = None;
+	let mut offset = 8; // Skip the signature.
+	while offset + 8 <= bytes.len() {
+		let length = u32::from_be_bytes([
+			bytes[offset],
+			bytes[offset + 1],
+			bytes[offset + 2],
+			bytes[offset + 3],
+		]) as usize;
+		let name = &bytes[offset + 4..offset + 8];
+		let data_start = offset + 8;
+		if data_start + length > bytes.len() {
+			return Err("truncated PNG chunk".to_string());
+		};
+		let data = &bytes[data_start..data_start + length];
+		offset = data_start + length + 4; // Chunk data plus its CRC.
+		let keyword_end = match data.iter().position(|byte| *byte == 0) {
+			Some(thing) => thing,
+			None => continue,
+		};
+		if &data[..keyword_end] != b"Description" {
+			continue;
+		};
+		let text = match name {
+			b"tEXt" => data[keyword_end + 1..].to_vec(),
+			// The keyword is followed by the compression method, a single byte.
+			b"zTXt" => inflate::inflate_bytes_zlib(&data[keyword_end + 2..])
+				.map_err(|e| format!("unable to decompress the metadata: {}", e))?,
+			_ => continue,
+		};
+		description = Some(String::from_utf8_lossy(&text).into_owned());
+	}
+	let description = match description {
+		Some(thing) => thing,
+		None => return Err("no DMI metadata".to_string()),
+	};
+
+	// Frames are read row by row, and laid out again in as many columns as rows, or one more.
+	let (width, height, frame_count) = sheet_layout(&description_lines(&description))?;
+	let columns = image.width() / width;
+	if columns == 0 || columns * (image.height() / height) < frame_count {
+		return Err(format!(
+			"a {}x{} sheet can't hold {} frames of {}x{}",
+			image.width(),
+			image.height(),
+			frame_count,
+			width,
+			height
+		));
+	};
+	let grid_size = (frame_count as f64).sqrt().ceil() as u32;
+	let mut frames_image = image::RgbaImage::new(grid_size * width, grid_size * height);
+	for frame in 0..frame_count {
+		let frame_image = image::imageops::crop_imm(
+			&image,
+			(frame % columns) * width,
+			(frame / columns) * height,
+			width,
+			height,
+		)
+		.to_image();
+		image::imageops::replace(
+			&mut frames_image,
+			&frame_image,
+			(frame % grid_size) * width,
+			(frame / grid_size) * height,
+		);
+	}
+	Ok(Dmi {
+		image: frames_image,
+		description,
+	})
+}
+
+///Returns the metadata lines, trimmed and without empty ones, so line endings and indentation don't matter.
+///Numbers are written out anew, so "1.0" and "1" match.
+fn description_lines(description: &str) -> Vec<String> {
+	description
+		.lines()
+		.map(|line| line.trim())
+		.filter(|line| !line.is_empty())
+		.map(|line| {
+			let (key, value) = match line.find(" = ") {
+				Some(index) => (&line[..index], &line[index + 3..]),
+				None => return line.to_string(),
+			};
+			let numbers: Result<Vec<f64>, _> = value
+				.split(',')
+				.map(|number| number.trim().parse::<f64>())
+				.collect();
+			match numbers {
+				Ok(numbers) => {
+					let numbers: Vec<String> =
+						numbers.iter().map(|number| number.to_string()).collect();
+					format!("{} = {}", key, numbers.join(","))
+				}
+				Err(_) => line.to_string(),
+			}
+		})
+		.collect()
+}
+
+///Saves the golden image, the produced one and a third one with every differing pixel highlighted, side by side.
+///Returns how many pixels differ.
+fn save_diff_image(
+	expected: &image::RgbaImage,
+	actual: &image::RgbaImage,
+	path: &Path,
+) -> Result<u32, String> {
+	let width = expected.width().max(actual.width());
+	let height = expected.height().max(actual.height());
+	let mut diff_image = image::RgbaImage::new(width * 3 + DIFF_GAP * 2, height);
+	image::imageops::replace(&mut diff_image, expected, 0, 0);
+	image::imageops::replace(&mut diff_image, actual, width + DIFF_GAP, 0);
+	let mut differing = 0;
+	// Pixels past the edge of the smaller image count as missing, and so as differing.
+	let pixel_at = |img: &image::RgbaImage, x: u32, y: u32| -> Option<image::Rgba<u8>> {
+		if x < img.width() && y < img.height() {
+			Some(*img.get_pixel(x, y))
+		} else {
+			None
+		}
+	};
+	for y in 0..height {
+		for x in 0..width {
+			let expected_pixel = pixel_at(expected, x, y);
+			let diff_pixel = match expected_pixel {
+				Some(mut faded) if expected_pixel == pixel_at(actual, x, y) => {
+					// Matching pixels are faded out, so the differing ones stand out while still showing where they are.
+					faded[3] /= 4;
+					faded
+				}
+				_ => {
+					differing += 1;
+					DIFF_COLOR
+				}
+			};
+			diff_image.put_pixel((width + DIFF_GAP) * 2 + x, y, diff_pixel);
+		}
+	}
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+	};
+	diff_image.save(path).map_err(|e| e.to_string())?;
+	Ok(differing)
+}
+
+///Compares a produced DMI against its golden, returning every difference found.
+fn compare_dmis(golden_path: &Path, produced_path: &Path, diff_path: &Path) -> Vec<String> {
+	let read = |path: &Path| -> Result<Dmi, String> {
+		let bytes = fs::read(path).map_err(|e| e.to_string())?;
+		read_dmi(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+	};
+	let (golden, produced) = match (read(golden_path), read(produced_path)) {
+		(Ok(golden), Ok(produced)) => (golden, produced),
+		(golden, produced) => {
+			return golden.err().into_iter().chain(produced.err()).collect();
+		}
+	};
+
+	let mut differences = vec![];
+	let golden_lines = description_lines(&golden.description);
+	let produced_lines = description_lines(&produced.description);
+	if golden_lines != produced_lines {
+		match golden_lines
+			.iter()
+			.zip(produced_lines.iter())
+			.position(|(golden_line, produced_line)| golden_line != produced_line)
+		{
+			Some(line) => differences.push(format!(
+				"metadata differs at line {}: expected \"{}\", got \"{}\"",
+				line + 1,
+				golden_lines[line],
+				produced_lines[line]
+			)),
+			None => differences.push(format!(
+				"metadata has {} lines instead of {}",
+				produced_lines.len(),
+				golden_lines.len()
+			)),
+		};
+	};
+	if golden.image.dimensions() != produced.image.dimensions() || golden.image != produced.image {
+		let difference = if golden.image.dimensions() == produced.image.dimensions() {
+			"pixels differ".to_string()
+		} else {
+			format!(
+				"pixels differ, the image is {}x{} instead of {}x{}",
+				produced.image.width(),
+				produced.image.height(),
+				golden.image.width(),
+				golden.image.height()
+			)
+		};
+		match save_diff_image(&golden.image, &produced.image, diff_path) {
+			Ok(differing) => differences.push(format!(
+				"{} ({} of them), see {}",
+				difference,
+				differing,
+				diff_path.display()
+			)),
+			Err(e) => differences.push(format!(
+				"{} (unable to save the diff image: {})",
+				difference, e
+			)),
+		};
+	};
+	differences
+}
+
+///Returns the file names in the directory, sorted, that pass the filter.
+fn file_names(dir: &Path, filter: impl Fn(&str) -> bool) -> Vec<String> {
+	let mut names: Vec<String> = match fs::read_dir(dir) {
+		Ok(entries) => entries
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.path().is_file())
+			.filter_map(|entry| entry.file_name().into_string().ok())
+			.filter(|name| filter(name))
+			.collect(),
+		Err(_) => vec![],
+	};
+	names.sort();
+	names
+}
+
+///Runs the cutter over the example, in a directory of its own, and returns the names of the DMIs produced there.
+fn run_example(example_dir: &Path, work_dir: &Path) -> Result<Vec<String>, String> {
+	if work_dir.exists() {
+		fs::remove_dir_all(work_dir).map_err(|e| e.to_string())?;
+	};
+	fs::create_dir_all(work_dir).map_err(|e| e.to_string())?;
+	// The config is read from the folder holding the executable, so each example gets a copy of it.
+	let cutter_path = Path::new(env!("CARGO_BIN_EXE_icon-cutter"));
+	let work_cutter_path = work_dir.join(cutter_path.file_name().unwrap());
+	fs::copy(cutter_path, &work_cutter_path).map_err(|e| e.to_string())?;
+	let example_files = file_names(example_dir, |_| true);
+	for name in example_files.iter() {
+		fs::copy(example_dir.join(name), work_dir.join(name)).map_err(|e| e.to_string())?;
+	}
+	let inputs: Vec<&String> = example_files
+		.iter()
+		.filter(|name| name.as_str() != "config.yaml")
+		.collect();
+
+	let mut child = Command::new(&work_cutter_path)
+		.args(inputs.iter())
+		.current_dir(work_dir)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.map_err(|e| format!("unable to run the cutter: {}", e))?;
+	// The cutter waits for a key press after each file, and once more before exiting.
+	if let Some(mut stdin) = child.stdin.take() {
+		let _ = stdin.write_all(&[b'\n'; 64]);
+	};
+	let output = child.wait_with_output().map_err(|e| e.to_string())?;
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	if !output.status.success()
+		|| stdout.contains("Error building icon")
+		|| stdout.contains("Failed to load configs")
+	{
+		return Err(format!(
+			"the cutter failed ({}):\n{}{}",
+			output.status,
+			stdout,
+			String::from_utf8_lossy(&output.stderr)
+		));
+	};
+
+	Ok(file_names(work_dir, |name| {
+		name.ends_with(".dmi") && !inputs.iter().any(|input| input.as_str() == name)
+	}))
+}
+
+///Replaces the goldens of the example with the DMIs just produced.
+fn bless(golden_dir: &Path, work_dir: &Path, produced: &[String]) -> Result<(), String> {
+	if golden_dir.exists() {
+		fs::remove_dir_all(golden_dir).map_err(|e| e.to_string())?;
+	};
+	fs::create_dir_all(golden_dir).map_err(|e| e.to_string())?;
+	for name in produced.iter() {
+		fs::copy(work_dir.join(name), golden_dir.join(name)).map_err(|e| e.to_string())?;
+	}
+	Ok(())
+}
+
+///Runs the example and compares its output against the goldens, returning every problem found.
+fn check_example(example_name: &str, blessing: bool) -> Vec<String> {
+	let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+	let example_dir = root.join("examples").join(example_name);
+	let golden_dir = root.join("tests").join("golden").join(example_name);
+	let scratch_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+	let work_dir = scratch_dir.join("work").join(example_name);
+	let diff_dir = scratch_dir.join("diffs").join(example_name);
+
+	let produced = match run_example(&example_dir, &work_dir) {
+		Ok(thing) => thing,
+		Err(e) => return vec![e],
+	};
+	if produced.is_empty() {
+		return vec!["no DMI was produced".to_string()];
+	};
+	if blessing {
+		return match bless(&golden_dir, &work_dir, &produced) {
+			Ok(()) => vec![],
+			Err(e) => vec![format!("unable to bless the goldens: {}", e)],
+		};
+	};
+
+	let goldens = file_names(&golden_dir, |name| name.ends_with(".dmi"));
+	if goldens.is_empty() {
+		return vec![format!(
+			"no goldens found in {}, run `{}` to create them",
+			golden_dir.display(),
+			BLESS_COMMAND
+		)];
+	};
+	let mut problems = vec![];
+	for name in goldens.iter() {
+		if !produced.contains(name) {
+			problems.push(format!("{} was not produced", name));
+			continue;
+		};
+		let diff_path = diff_dir.join(format!("{}.png", name));
+		for difference in compare_dmis(&golden_dir.join(name), &work_dir.join(name), &diff_path) {
+			problems.push(format!("{}: {}", name, difference));
+		}
+	}
+	for name in produced.iter().filter(|name| !goldens.contains(name)) {
+		problems.push(format!("{} was produced, but has no golden", name));
+	}
+	problems
+}
+
+///Returns the names of every folder under examples/ holding a config, sorted.
+fn example_names() -> Vec<String> {
+	let examples_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+	let mut examples: Vec<String> = fs::read_dir(&examples_dir)
+		.unwrap()
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.path().join("config.yaml").is_file())
+		.filter_map(|entry| entry.file_name().into_string().ok())
+		.collect();
+	examples.sort();
+	assert!(
+		!examples.is_empty(),
+		"No examples found in {}",
+		examples_dir.display()
+	);
+	examples
+}
+
+#[test]
+fn examples_match_goldens() {
+	let blessing = std::env::var_os(BLESS_VAR).is_some();
+	let examples = example_names();
+
+	let mut failures = vec![];
+	for example in examples.iter() {
+		let problems = check_example(example, blessing);
+		if problems.is_empty() {
+			continue;
+		};
+		failures.push(format!("{}:\n\t{}", example, problems.join("\n\t")));
+	}
+	assert!(
+		failures.is_empty(),
+		"{} of {} examples don't match their goldens. If the changes are intended, run `{}` to bless them.\n{}",
+		failures.len(),
+		examples.len(),
+		BLESS_COMMAND,
+		failures.join("\n")
+	);
+	if blessing {
+		println!("Blessed the goldens of {} examples.", examples.len());
+	};
+}
+
+#[test]
+fn examples_are_reproducible() {
+	// Unlike the goldens, the bytes themselves are compared here, as the output is meant not to churn in version control.
+	let examples = example_names();
+	let scratch_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+		.join("golden")
+		.join("reproducible");
+	let mut failures = vec![];
+	for example in examples.iter() {
+		let example_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+			.join("examples")
+			.join(example);
+		let first_dir = scratch_dir.join("first").join(example);
+		let second_dir = scratch_dir.join("second").join(example);
+		let (first, second) = match (
+			run_example(&example_dir, &first_dir),
+			run_example(&example_dir, &second_dir),
+		) {
+			(Ok(first), Ok(second)) => (first, second),
+			(first, second) => {
+				failures.extend(
+					first
+						.err()
+						.into_iter()
+						.chain(second.err())
+						.map(|e| format!("{}: {}", example, e)),
+				);
+				continue;
+			}
+		};
+		if first != second {
+			failures.push(format!(
+				"{}: produced {:?}, then {:?}",
+				example, first, second
+			));
+			continue;
+		};
+		for name in first.iter() {
+			if fs::read(first_dir.join(name)).ok() != fs::read(second_dir.join(name)).ok() {
+				failures.push(format!("{}: {} differs between runs", example, name));
+			};
+		}
+	}
+	assert!(
+		failures.is_empty(),
+		"Running the same example twice gave different bytes:\n{}",
+		failures.join("\n")
+	);
+}